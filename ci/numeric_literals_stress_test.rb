@@ -0,0 +1,36 @@
+p 0
+p 1234
+p 12345
+p 123456789012345678901234567890
+p -1234567
+p 1_0000_0000
+p 0d1234567
+p 0D1234567
+p 0xff
+p 0XFF
+p 0xDEAD_BEEF
+p -0X1F
+p 0b1010
+p 0B1010_1010
+p 0o755
+p 0O755
+p 0755
+p 0_755
+p 3.14159
+p 1234567.5
+p 1E5
+p 1e-5
+p 1.5E+10
+p 2.5E-3
+p 1234567r
+p 0x1Fr
+p 1.5r
+p 1000000i
+p 1E5i
+p 0XFFi
+p 1.5ri
+p 12345ri
+p [100000, 0XAB, 1E2]
+p({ 1000000 => 0B11 })
+p 10000.times.count
+p 1000000.to_s
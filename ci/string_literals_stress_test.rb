@@ -118,3 +118,20 @@ foo
 puts <<EOD.gsub("a", "b")
 "cde"
 EOD
+
+puts "it's"
+puts 'it\'s'
+puts "\#{not interpolated} \#@foo"
+puts "tab\tand\e"
+puts "unicode \u00e9"
+puts 'é'
+puts "line \
+continued"
+puts 'back\slash \\ and \n'
+puts "trailing backslash \\"
+puts 'trailing backslash \\'
+puts :'single symbol'
+puts :"double \"symbol\""
+puts %q(it's %q)
+puts "#{'nested'} #{"nested"}"
+puts({"label": 1, 'single label': 2})
@@ -0,0 +1,20 @@
+class Foo
+  attr_reader :a
+
+  def a
+  end
+
+  private
+
+    # The b
+    def b
+    end
+
+    def c = 1
+
+  # Protected things
+  protected
+
+    attr_reader :d
+    # trailing
+end
@@ -0,0 +1,32 @@
+class Foo
+  def a
+  end
+
+  private
+
+  # The b
+  def b
+  end
+
+  def c
+  end
+
+  protected
+
+  attr_reader :d
+
+  def e
+  end
+
+  public
+
+  def f
+  end
+end
+
+module Bar
+  private
+
+  def self.a
+  end
+end
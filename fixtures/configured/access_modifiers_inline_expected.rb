@@ -0,0 +1,28 @@
+class Foo
+  def a
+  end
+
+  # The b
+  private def b
+  end
+
+  private def c
+  end
+
+  protected
+
+  attr_reader :d
+
+  def e
+  end
+
+  public def f
+  end
+end
+
+module Bar
+  private
+
+  def self.a
+  end
+end
@@ -0,0 +1,22 @@
+class Foo
+  private def a
+  end
+
+  def b
+  end
+
+  # The c
+  private def c
+  end
+
+  private def d
+  end
+
+  protected def e
+  end
+end
+
+class Bar
+  private def self.a
+  end
+end
@@ -0,0 +1,22 @@
+class Foo
+  def a
+  end
+
+  private
+
+  def b
+  end
+
+  protected
+
+  # a comment
+  def c
+  end
+
+  public
+
+  def d
+  end
+
+  private
+end
@@ -0,0 +1,38 @@
+class Foo
+  def a
+  end
+
+  private
+
+  def b
+  end
+
+  protected
+
+  # a comment
+  def c
+  end
+
+  public
+
+  def d
+  end
+
+  private
+end
+
+class Bar
+  private
+
+  attr_reader :a
+
+  public
+
+  attr_reader :b
+
+  # Helpers
+  private
+
+  def c
+  end
+end
@@ -0,0 +1,17 @@
+class Foo
+  def a = 1
+
+  def b = 2
+
+  # about c
+  def self.c = 3
+
+  def d
+  end
+
+  def e
+  end
+
+  LIMIT = 4
+  def f = 5
+end
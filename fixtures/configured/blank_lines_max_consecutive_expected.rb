@@ -0,0 +1,14 @@
+a = 1
+
+
+b = 2
+
+
+c = 3
+
+d = 4
+# one
+
+
+# two
+e = 5
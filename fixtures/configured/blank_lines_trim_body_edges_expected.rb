@@ -0,0 +1,13 @@
+module Outer
+  class Foo
+    def a
+      do_something
+    end
+
+    def b
+      x = 1
+
+      y = 2
+    end
+  end
+end
@@ -0,0 +1,17 @@
+items.each { |item|
+  puts(item)
+  save(item)
+}
+
+totals = orders.map { |order|
+  tax = order.total * rate
+  order.total + tax
+}
+
+names = users.map { |user| user.name }
+users.each do |user| notify(user) end
+
+# `do` would pass the block to `puts`
+puts items.map { |item|
+  item.name
+}
@@ -0,0 +1,19 @@
+items.each do |item|
+  puts(item)
+  save(item)
+end
+
+totals = orders.map do |order|
+  tax = order.total * rate
+  order.total + tax
+end
+
+names = users.map { |user| user.name }
+users.each { |user| notify(user) }
+
+# `do` would pass the block to `puts`
+puts(
+  items.map { |item|
+    item.name
+  }
+)
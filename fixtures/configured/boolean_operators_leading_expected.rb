@@ -0,0 +1,27 @@
+if foo || bar
+end
+
+if very_long_conditional? \
+    || even_longer_conditional? \
+    || man_why_is_this_line_so_long? \
+    || paul_blart_is_a_classic_of_american_cinema!
+  foo
+end
+
+unless user_is_signed_in? \
+    && user_has_confirmed_their_email_address_recently? \
+    && user_is_not_banned_from_the_discussion_forum?
+  redirect_to_sign_in
+end
+
+while queue_has_pending_jobs? \
+    && worker_has_not_been_asked_to_shut_down? \
+    && deadline_has_not_passed?
+  work_off_next_job
+end
+
+def bees!
+  more_bees? ||
+    # Should there be less bees?
+    less_bees?
+end
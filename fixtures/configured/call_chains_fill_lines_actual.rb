@@ -0,0 +1,9 @@
+result = some_collection_with_a_long_name.select(&:active?).map(&:normalized_value).reject(&:blank?).uniq.sort_by(&:length).first(10).each_slice(2).to_a
+
+foo.bar
+  .baz
+
+query
+  # Only the ones people can see
+  .where(visible: true)
+  .order(:name)
@@ -0,0 +1,20 @@
+foo.bar
+  .baz
+
+def example
+  things
+    .map do |thing|
+      thing.call
+    end
+    .uniq
+end
+
+foo.bar.baz
+
+Class&.new
+  &.call!
+
+query
+  # Only the ones people can see
+  .where(visible: true)
+  .order(:name)
@@ -0,0 +1,55 @@
+class Calculator
+  # Adds two numbers.
+  #
+  # @example Adding numbers
+  #   calc = Calculator.new
+  #   calc.add 1,2 # => 3
+  #   calc.add 3,4   # => 7
+  #
+  # @example Across lines
+  #   calc.add 1,
+  #     2 # => 3
+  #
+  # @param a [Integer] the first number,
+  #   which is indented but isn't code
+  # @return [Integer]
+  def add(a, b)
+    a + b
+  end
+
+  # Subtracts two numbers, e.g.
+  #
+  #     calc.subtract(3,1)
+  #     calc.subtract 5,
+  #       2
+  #
+  # But not this:
+  #
+  #     this is not ruby (
+  #
+  # Nor this:
+  #
+  # ```
+  #     calc.subtract 3,1
+  # ```
+  def subtract(a, b)
+    a - b
+  end
+
+  # Multiplies two numbers.
+  #
+  # * Works with any numbers,
+  #
+  #     see also divide
+  #
+  # @return [Integer] the product,
+  #
+  #     see also divide
+  #
+  # But this is an example:
+  #
+  #     calc.multiply 2,3
+  def multiply(a, b)
+    a * b
+  end
+end
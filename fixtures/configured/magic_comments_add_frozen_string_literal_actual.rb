@@ -0,0 +1,7 @@
+#!/usr/bin/env ruby
+# encoding: utf-8
+
+require "foo"
+
+class Foo
+end
@@ -0,0 +1,8 @@
+#!/usr/bin/env ruby
+# encoding: utf-8
+# frozen_string_literal: true
+
+require "foo"
+
+class Foo
+end
@@ -0,0 +1,3 @@
+# -*- coding: utf-8; frozen_string_literal: false -*-
+# Warn-Indent: TRUE
+puts "hi"
@@ -0,0 +1,4 @@
+# -*- coding: utf-8; frozen_string_literal: false -*-
+# warn_indent: true
+
+puts "hi"
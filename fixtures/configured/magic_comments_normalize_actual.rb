@@ -0,0 +1,6 @@
+#!/usr/bin/env ruby
+# -*- coding: UTF-8 -*-
+# Frozen-String-Literal: TRUE
+# warn_indent: true
+# Prints a greeting
+puts "hi"
@@ -0,0 +1,7 @@
+#!/usr/bin/env ruby
+# encoding: utf-8
+# frozen_string_literal: true
+# warn_indent: true
+
+# Prints a greeting
+puts "hi"
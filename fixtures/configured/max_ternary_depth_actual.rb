@@ -0,0 +1,9 @@
+size = width > 1000 ? :large : width > 500 ? :medium : :small
+
+size ||= width > 1000 ? :large : :small
+
+def size
+  width > 1000 ? :large : width > 500 ? :medium : :small
+end
+
+puts(width > 1000 ? :large : width > 500 ? :medium : :small)
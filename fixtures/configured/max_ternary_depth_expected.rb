@@ -0,0 +1,21 @@
+size = if width > 1000
+  :large
+elsif width > 500
+  :medium
+else
+  :small
+end
+
+size ||= width > 1000 ? :large : :small
+
+def size
+  if width > 1000
+    :large
+  elsif width > 500
+    :medium
+  else
+    :small
+  end
+end
+
+puts(width > 1000 ? :large : width > 500 ? :medium : :small)
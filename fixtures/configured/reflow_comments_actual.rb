@@ -0,0 +1,20 @@
+# A short comment stays exactly
+# the way it was written.
+
+# This paragraph is far too long to fit on one line, so rubyfmt wraps it at the maximum line length and joins the rest of the
+# paragraph back up with it.
+#
+#   # Code examples are left alone, even when they are long enough that they would otherwise need to be wrapped by rubyfmt
+#   foo(bar)
+#
+# - List items are left alone too, even when they are long enough that they would otherwise need to be wrapped by rubyfmt
+# | a | table | row | that | goes | on | and | on | and | on | and | on | and | on | and | on | and | on | and | on | and |
+#
+# @param foo [String] YARD tags are left alone too, even when they are long enough that they would otherwise need wrapping
+# rubocop:disable Metrics/AbcSize, Metrics/MethodLength, Metrics/CyclomaticComplexity, Metrics/PerceivedComplexity, Style/Documentation
+class Foo
+  # Comments keep their indentation when they're wrapped, so this one that's nested inside of a class still lines up with
+  # the method below it.
+  def foo
+  end
+end
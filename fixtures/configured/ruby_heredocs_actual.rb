@@ -0,0 +1,25 @@
+BAR = <<~RUBY
+  def bar(a,b)
+    [a,b].map { |x| x*2 }
+  end
+RUBY
+
+BAZ = <<~'RUBY'
+  def baz; 'baz'; end
+RUBY
+
+QUERY = <<~SQL
+  select  *  from  foo
+SQL
+
+INTERPOLATED = <<~RUBY
+  def #{name}(a,b); end
+RUBY
+
+ESCAPED = <<~RUBY
+  def qux(a,b) = "\\n"
+RUBY
+
+BROKEN = <<~RUBY
+  def broken(a,b
+RUBY
@@ -0,0 +1,17 @@
+require "rails"
+require "active_record" # loaded by rails anyway
+require("action_controller")
+require "zeitwerk" if ENV["ZEITWERK"]
+require "bundler"
+require 'bootsnap/setup'
+
+require_relative "support/z_helpers"
+# shared examples
+require_relative "support/a_examples"
+
+module App
+  require "json"
+  require "csv"
+
+  VERSION = "1"
+end
@@ -0,0 +1,18 @@
+require("action_controller")
+# loaded by rails anyway
+require "active_record"
+require "rails"
+require "zeitwerk" if ENV["ZEITWERK"]
+require "bootsnap/setup"
+require "bundler"
+
+# shared examples
+require_relative "support/a_examples"
+require_relative "support/z_helpers"
+
+module App
+  require "csv"
+  require "json"
+
+  VERSION = "1"
+end
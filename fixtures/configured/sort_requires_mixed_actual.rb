@@ -0,0 +1,13 @@
+require_relative "lib/widget"
+require "json"
+# for the CLI
+require_relative "lib/cli"
+require "optparse"
+
+Widget.run
+
+module Cli
+  require_relative "cli/options"
+  require "optparse"
+  VERSION = "1"
+end
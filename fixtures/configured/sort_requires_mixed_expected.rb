@@ -0,0 +1,14 @@
+require "json"
+require "optparse"
+# for the CLI
+require_relative "lib/cli"
+require_relative "lib/widget"
+
+Widget.run
+
+module Cli
+  require "optparse"
+  require_relative "cli/options"
+
+  VERSION = "1"
+end
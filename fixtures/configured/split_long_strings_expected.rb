@@ -0,0 +1,11 @@
+def unknown_argument_message
+  message = "This method was called with an argument that it does not know how to handle, please check the " \
+    "documentation for the list of arguments that it accepts"
+end
+
+SHORT_MESSAGE = "Strings that fit on one line are left alone"
+
+WITH_ESCAPES = "Escapes like \t and \u{1F600 1F601} are never split in the middle, strings are only ever split after " \
+  "a space between two words"
+
+INTERPOLATED = "Strings with interpolation like #{this_one} are left alone, even when they run past the maximum line length of the file"
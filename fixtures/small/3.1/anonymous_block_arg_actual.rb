@@ -0,0 +1,9 @@
+def foo(&) = bar(&)
+
+def baz(a,*,**,&)
+  qux(a,&)
+end
+
+def quux(&)
+  items.each(&)
+end
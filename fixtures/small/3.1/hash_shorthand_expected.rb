@@ -0,0 +1,6 @@
+x = 1
+y = 2
+
+h = {x:, y:}
+foo(x:, y:)
+bar("a" => 1, x:)
@@ -0,0 +1,5 @@
+def foo(*, **) = bar(*, **)
+
+def baz(*, **)
+  qux(1, *, **)
+end
@@ -0,0 +1,6 @@
+items.map { it.name }
+items.each { it() }
+items.each do
+  puts it
+end
+-> { foo it }
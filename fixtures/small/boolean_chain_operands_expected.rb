@@ -0,0 +1,9 @@
+if first_value_with_a_long_name + second_value_with_a_long_name > maximum_allowed &&
+    other_condition_that_also_has_to_be_true?
+  foo
+end
+
+while retries_so_far * backoff_in_seconds < timeout_in_seconds ||
+    still_waiting_on_the_first_response_from_the_remote_server?
+  retry!
+end
@@ -0,0 +1,11 @@
+# Inside a block without params, `it()` has to keep its parens, since
+# a bare `it` would read the block's implicit param on Ruby 3.4
+items.each { it() }
+items.each do
+  it()
+end
+-> { it() }
+
+# Explicit params don't bind `it`, and neither does the enclosing scope
+items.each { |item| it }
+it
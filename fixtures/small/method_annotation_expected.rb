@@ -2,17 +2,21 @@ sig { void }
 def empty_example
 end
 
-sig {
-  params(foo: SomePrettyLongClassName, bar: AnEvenLongerClassName::ThatMakesThisGoPrettyFar, baz: Hasdfasdfasdfasdas)
-}
+sig do
+  params(
+    foo: SomePrettyLongClassName,
+    bar: AnEvenLongerClassName::ThatMakesThisGoPrettyFar,
+    baz: Hasdfasdfasdfasdas
+  )
+end
 def do_stuff!(foo, bar, baz)
 end
 
-sig {
+sig do
   # This method doesn't return anything weeeeee
   void
   # But you can bet it has some side effects
-}
+end
 def do_stuff!
 end
 
@@ -90,7 +94,7 @@ private def my_method
 end
 
 class Bees
-  sig {
+  sig do
     # These are the params
     params(
       first_param: MyClass,
@@ -99,7 +103,7 @@ class Bees
     )
       .void
     # Please not the bees!
-  }
+  end
   def not_the_bees!
   end
 end
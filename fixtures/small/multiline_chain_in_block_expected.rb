@@ -1,9 +1,9 @@
-sig {
+sig do
   params(
     route: String
   )
     .void
-}
+end
 def ajax_get(route)
   super
 end
@@ -0,0 +1,16 @@
+items.each { puts(_1) }
+pairs.each do
+  puts(_1, _2)
+end
+items.map { _1.call!(_2) }
+
+# Inside a block without params, calls to methods named like numbered
+# params have to keep their parens, or they'd read the params instead
+items.each { _1() }
+pairs.each do
+  puts(_2())
+end
+-> { _1() }
+
+# Outside of one they don't
+_1
@@ -0,0 +1,23 @@
+class Widget
+  extend T::Sig
+
+  sig { params(name: String).returns(String) }
+  def greet(name)
+    "Hello, #{name}"
+  end
+
+  sig { params(first_argument: Integer, second_argument: T.nilable(String), third_arg: Symbol).returns(T::Array[String]) }
+  def build(first_argument, second_argument, third_arg)
+    []
+  end
+
+  sig(:final) {
+    params(first_argument: Integer, second_argument: T.nilable(String), third_arg: Symbol).void
+  }
+  def final_build(first_argument, second_argument, third_arg)
+  end
+
+  sig { params(callback_name: Symbol, fallback_value: Integer).returns(T.proc.params(value: Integer, other_value: T.nilable(String)).void) }
+  def callback_for(callback_name, fallback_value)
+  end
+end
@@ -0,0 +1,38 @@
+class Widget
+  extend T::Sig
+
+  sig { params(name: String).returns(String) }
+  def greet(name)
+    "Hello, #{name}"
+  end
+
+  sig do
+    params(
+      first_argument: Integer,
+      second_argument: T.nilable(String),
+      third_arg: Symbol
+    ).returns(T::Array[String])
+  end
+  def build(first_argument, second_argument, third_arg)
+    []
+  end
+
+  sig(:final) do
+    params(
+      first_argument: Integer,
+      second_argument: T.nilable(String),
+      third_arg: Symbol
+    ).void
+  end
+  def final_build(first_argument, second_argument, third_arg)
+  end
+
+  sig do
+    params(
+      callback_name: Symbol,
+      fallback_value: Integer
+    ).returns(T.proc.params(value: Integer, other_value: T.nilable(String)).void)
+  end
+  def callback_for(callback_name, fallback_value)
+  end
+end
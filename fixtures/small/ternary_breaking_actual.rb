@@ -0,0 +1,5 @@
+def status_label
+  label = account.suspended_for_billing_reasons? ? I18n.t("accounts.status.suspended_for_billing") : I18n.t("accounts.status.active")
+end
+
+short = admin? ? "yes" : "no"
@@ -27,6 +27,15 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
                 .map(|(i, field)| Ident::new(&format!("field{}", i), field.ty.span()))
                 .collect::<Vec<_>>();
 
+            // Variants without fields match `nil`
+            if field_names.is_empty() {
+                return quote! {
+                    if let Ok(()) = <() as serde::Deserialize>::deserialize(deserializer) {
+                        return Ok(#enum_name::#ident);
+                    }
+                };
+            }
+
             quote! {
                 if let Ok((#(#field_names),*)) = serde::Deserialize::deserialize(deserializer) {
                     return Ok(#enum_name::#ident(#(#field_names),*));
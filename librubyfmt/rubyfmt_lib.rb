@@ -447,6 +447,8 @@ class Parser < Ripper::SexpBuilderPP
       end_delim, end_line = @string_stack.pop
       start_delim, start_line = @string_stack.pop
 
+      # Keep the original delimiter around so that quote styles can be preserved
+      args << start_delim
       args << [start_line, end_line]
 
       clean_string_content(start_delim, end_delim, args[0])
@@ -493,7 +495,7 @@ class Parser < Ripper::SexpBuilderPP
     delim, start_line = @string_stack.pop
     res = super
     clean_string_content(delim, delim, res[1])
-    res + [[start_line, lineno]]
+    res + [delim, [start_line, lineno]]
   end
 
   def on_regexp_beg(re_part)
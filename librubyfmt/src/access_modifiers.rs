@@ -0,0 +1,155 @@
+use crate::ripper_tree_types::*;
+use crate::types::LineNumber;
+
+fn is_access_modifier_name(name: &str) -> bool {
+    name == "private" || name == "protected" || name == "public"
+}
+
+/// The name and line of a bare `private`, `protected`, or `public`. Expects
+/// an expression that's already been through `normalize`.
+pub fn bare_access_modifier(expression: &Expression) -> Option<(String, LineNumber)> {
+    match expression {
+        Expression::MethodCall(MethodCall(
+            _,
+            chain,
+            IdentOrOpOrKeywordOrConst::Ident(Ident(_, name, linecol)),
+            false,
+            ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(args),
+            _,
+        )) if chain.is_empty() && args.is_empty() && is_access_modifier_name(name) => {
+            Some((name.clone(), linecol.0))
+        }
+        _ => None,
+    }
+}
+
+// `private def foo`, but not `private def self.foo`, since the modifier
+// doesn't apply to singleton methods
+fn is_inline_access_modifier(expression: &Expression) -> bool {
+    match expression {
+        Expression::MethodCall(MethodCall(
+            _,
+            chain,
+            IdentOrOpOrKeywordOrConst::Ident(Ident(_, name, _)),
+            _,
+            ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(args),
+            _,
+        )) => {
+            chain.is_empty()
+                && is_access_modifier_name(name)
+                && matches!(args.as_slice(), [Expression::Def(_)])
+        }
+        _ => false,
+    }
+}
+
+fn into_inline_parts(expression: Expression) -> (String, Def) {
+    match expression {
+        Expression::MethodCall(MethodCall(
+            _,
+            _,
+            name,
+            _,
+            ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(mut args),
+            _,
+        )) => match args.pop() {
+            Some(Expression::Def(def)) => (name.get_name(), def),
+            _ => panic!("expected an inline access modifier"),
+        },
+        _ => panic!("expected an inline access modifier"),
+    }
+}
+
+fn bare_access_modifier_call(name: String, line: LineNumber) -> Expression {
+    Expression::MethodCall(MethodCall::new(
+        vec![],
+        IdentOrOpOrKeywordOrConst::Ident(Ident::new(name, LineCol(line, 0))),
+        false,
+        ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(vec![]),
+        Some(StartEnd(line, line)),
+    ))
+}
+
+fn inline_access_modifier_call(name: String, def: Def) -> Expression {
+    let start_end = def.4.clone();
+    Expression::MethodCall(MethodCall::new(
+        vec![],
+        IdentOrOpOrKeywordOrConst::Ident(Ident::new(name, LineCol(start_end.start_line(), 0))),
+        false,
+        ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(vec![Expression::Def(def)]),
+        Some(start_end),
+    ))
+}
+
+/// Rewrites sections that start with a bare modifier into `private def` on
+/// each method. A section is only rewritten when it's nothing but instance
+/// methods, since the bare call also applies to e.g. `attr_reader`.
+pub fn access_modifiers_to_inline(expressions: Vec<Expression>) -> Vec<Expression> {
+    let mut result = Vec::with_capacity(expressions.len());
+    // The visibility a plain `def` gets at this point in the output
+    let mut visibility = "public".to_string();
+    let mut expressions = expressions.into_iter().peekable();
+    while let Some(expression) = expressions.next() {
+        let name = match bare_access_modifier(&expression) {
+            Some((name, _)) => name,
+            None => {
+                result.push(expression);
+                continue;
+            }
+        };
+
+        let mut section = Vec::new();
+        while let Some(next) = expressions.next_if(|e| bare_access_modifier(e).is_none()) {
+            section.push(next);
+        }
+
+        let only_methods = section.iter().any(|e| matches!(e, Expression::Def(_)))
+            && section
+                .iter()
+                .all(|e| matches!(e, Expression::Def(_) | Expression::VoidStmt(_)));
+        if !only_methods {
+            visibility = name;
+            result.push(expression);
+            result.extend(section);
+            continue;
+        }
+
+        for expression in section {
+            match expression {
+                Expression::Def(def) if name != visibility => {
+                    result.push(inline_access_modifier_call(name.clone(), def))
+                }
+                expression => result.push(expression),
+            }
+        }
+    }
+    result
+}
+
+/// Rewrites `private def` calls into a bare modifier followed by the methods.
+/// Only the calls at the end of the body are rewritten, since a bare modifier
+/// also applies to everything that comes after it.
+pub fn access_modifiers_to_sections(mut expressions: Vec<Expression>) -> Vec<Expression> {
+    let trailing = expressions
+        .iter()
+        .rev()
+        .take_while(|e| is_inline_access_modifier(e) || matches!(e, Expression::VoidStmt(_)))
+        .count();
+    let trailing = expressions.split_off(expressions.len() - trailing);
+
+    let mut current_section = None;
+    for expression in trailing {
+        if !is_inline_access_modifier(&expression) {
+            expressions.push(expression);
+            continue;
+        }
+
+        let (name, def) = into_inline_parts(expression);
+        if current_section.as_ref() != Some(&name) {
+            expressions.push(bare_access_modifier_call(name.clone(), def.4.start_line()));
+            current_section = Some(name);
+        }
+        expressions.push(Expression::Def(def));
+    }
+    expressions
+}
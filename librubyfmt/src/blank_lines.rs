@@ -0,0 +1,222 @@
+use crate::format_options::BlankLineOptions;
+use crate::line_tokens::{join_lines, split_lines, ConcreteLineToken, Line};
+
+/// Applies the configured blank line rules to the rendered output. This works
+/// on whole lines, heredoc bodies and multiline strings are a single token
+/// so their contents are never touched.
+pub fn normalize_blank_lines(
+    tokens: Vec<ConcreteLineToken>,
+    options: BlankLineOptions,
+) -> Vec<ConcreteLineToken> {
+    let mut lines = split_lines(tokens);
+    // Everything after `__END__` is data
+    let data_start = lines
+        .iter()
+        .position(|l| l.contains(&ConcreteLineToken::DataEnd))
+        .unwrap_or(lines.len());
+    let mut data = lines.split_off(data_start);
+    if data.is_empty() {
+        // The empty "line" after the final newline isn't a blank line
+        data.extend(lines.pop());
+    }
+
+    if options.trim_body_edges {
+        lines = trim_body_edges(lines);
+    }
+    lines = limit_consecutive_blank_lines(lines, options.max_consecutive);
+    if options.before_access_modifiers || options.after_access_modifiers {
+        lines = blank_lines_around_access_modifiers(
+            lines,
+            options.before_access_modifiers,
+            options.after_access_modifiers,
+        );
+    }
+    if options.between_methods {
+        lines = blank_line_between_methods(lines);
+    }
+
+    lines.extend(data);
+    join_lines(lines)
+}
+
+fn is_blank(line: &[ConcreteLineToken]) -> bool {
+    line.iter().all(|t| match t {
+        ConcreteLineToken::Indent { .. } => true,
+        // Blank lines between comments are empty comments
+        ConcreteLineToken::Comment { contents } => contents.is_empty(),
+        _ => false,
+    })
+}
+
+fn is_comment(line: &[ConcreteLineToken]) -> bool {
+    matches!(line, [ConcreteLineToken::Comment { contents }] if !contents.is_empty())
+}
+
+// The first real token and the indentation of the line it's on. Heredoc
+// bodies and anything else without an indent don't have a depth
+fn first_token(line: &[ConcreteLineToken]) -> Option<(u32, &ConcreteLineToken)> {
+    match line {
+        [ConcreteLineToken::Indent { depth }, first, ..] => Some((*depth, first)),
+        [ConcreteLineToken::Comment { contents }] if !contents.is_empty() => {
+            let depth = contents.len() - contents.trim_start().len();
+            Some((depth as u32, &line[0]))
+        }
+        _ => None,
+    }
+}
+
+fn opens_body(token: &ConcreteLineToken) -> bool {
+    matches!(
+        token,
+        ConcreteLineToken::ClassKeyword
+            | ConcreteLineToken::ModuleKeyword
+            | ConcreteLineToken::DefKeyword
+    )
+}
+
+fn trim_body_edges(lines: Vec<Line>) -> Vec<Line> {
+    let mut keep = vec![true; lines.len()];
+
+    for (idx, line) in lines.iter().enumerate() {
+        let (depth, first) = match first_token(line) {
+            Some(t) => t,
+            None => continue,
+        };
+
+        if opens_body(first) {
+            // Endless and single line defs are followed by something at the
+            // same depth, anything else is followed by its body or its `end`
+            let next = lines[idx + 1..].iter().position(|l| !is_blank(l));
+            if let Some(next) = next {
+                let next_line = &lines[idx + 1 + next];
+                let starts_body = match first_token(next_line) {
+                    Some((next_depth, next_first)) => {
+                        next_depth > depth
+                            || (next_depth == depth && next_first == &ConcreteLineToken::End)
+                    }
+                    None => false,
+                };
+                if starts_body {
+                    keep[idx + 1..idx + 1 + next].fill(false);
+                }
+            }
+        } else if first == &ConcreteLineToken::End {
+            // Walk back over the body to the line that opened it
+            let mut opener = None;
+            for prev in (0..idx).rev() {
+                if is_blank(&lines[prev]) {
+                    continue;
+                }
+                match first_token(&lines[prev]) {
+                    Some((prev_depth, _)) if prev_depth > depth => {}
+                    Some((prev_depth, prev_first)) if prev_depth == depth => {
+                        opener = Some(prev_first);
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+
+            if opener.map(opens_body).unwrap_or(false) {
+                let last_body_line = (0..idx)
+                    .rev()
+                    .find(|&i| !is_blank(&lines[i]))
+                    .expect("the opener isn't blank");
+                keep[last_body_line + 1..idx].fill(false);
+            }
+        }
+    }
+
+    lines
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(line, keep)| if keep { Some(line) } else { None })
+        .collect()
+}
+
+fn limit_consecutive_blank_lines(lines: Vec<Line>, max: usize) -> Vec<Line> {
+    let mut blank_run = 0;
+    lines
+        .into_iter()
+        .filter(|line| {
+            if is_blank(line) {
+                blank_run += 1;
+                blank_run <= max
+            } else {
+                blank_run = 0;
+                true
+            }
+        })
+        .collect()
+}
+
+fn is_access_modifier(line: &[ConcreteLineToken]) -> bool {
+    matches!(
+        line,
+        [
+            ConcreteLineToken::Indent { .. },
+            ConcreteLineToken::AccessModifier { .. }
+        ]
+    )
+}
+
+fn blank_lines_around_access_modifiers(lines: Vec<Line>, before: bool, after: bool) -> Vec<Line> {
+    let mut result: Vec<Line> = Vec::with_capacity(lines.len());
+    let mut after_modifier = false;
+    for line in lines {
+        if before && is_access_modifier(&line) {
+            // Not between the modifier and the line opening its class, or
+            // the comments describing it
+            let follows_body_start = match (result.last(), first_token(&line)) {
+                (Some(prev), Some((depth, _))) => {
+                    is_comment(prev)
+                        || first_token(prev)
+                            .map(|(prev_depth, _)| prev_depth < depth)
+                            .unwrap_or(false)
+                }
+                _ => true,
+            };
+            if !follows_body_start && !result.last().map(|l| is_blank(l)).unwrap_or(true) {
+                result.push(Vec::new());
+            }
+        }
+
+        // No blank line between a modifier and the `end` of its class
+        let closes_body = matches!(first_token(&line), Some((_, ConcreteLineToken::End)));
+        if after_modifier && !is_blank(&line) && !closes_body {
+            result.push(Vec::new());
+        }
+        after_modifier = after && is_access_modifier(&line);
+        result.push(line);
+    }
+    result
+}
+
+fn blank_line_between_methods(lines: Vec<Line>) -> Vec<Line> {
+    let mut result: Vec<Line> = Vec::with_capacity(lines.len());
+    for line in lines {
+        if let Some((depth, ConcreteLineToken::DefKeyword)) = first_token(&line) {
+            // Comments directly above a def stay with it
+            let comments_start =
+                result.len() - result.iter().rev().take_while(|l| is_comment(l)).count();
+            let follows_method = match comments_start
+                .checked_sub(1)
+                .and_then(|prev| first_token(&result[prev]))
+            {
+                Some((prev_depth, prev_first)) => {
+                    prev_depth == depth
+                        && matches!(
+                            prev_first,
+                            ConcreteLineToken::End | ConcreteLineToken::DefKeyword
+                        )
+                }
+                None => false,
+            };
+            if follows_method {
+                result.insert(comments_start, Vec::new());
+            }
+        }
+        result.push(line);
+    }
+    result
+}
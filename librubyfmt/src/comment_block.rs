@@ -50,6 +50,10 @@ impl CommentBlock {
         !self.comments.is_empty()
     }
 
+    pub fn into_comments(self) -> Vec<String> {
+        self.comments
+    }
+
     pub fn len(&self) -> usize {
         self.comments.len()
     }
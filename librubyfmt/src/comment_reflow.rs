@@ -0,0 +1,127 @@
+use crate::line_tokens::{join_lines, split_lines, ConcreteLineToken, Line};
+use crate::magic_comments::is_magic_comment_text;
+use crate::render_queue_writer::MAX_LINE_LENGTH;
+
+/// Rewraps paragraphs of prose in comments when one of their lines runs past
+/// the maximum line length. Anything that might be laid out on purpose (code
+/// examples, YARD tags, lists, tables, and directives) is left alone, and so
+/// are paragraphs that already fit.
+pub fn reflow_comments(tokens: Vec<ConcreteLineToken>) -> Vec<ConcreteLineToken> {
+    let mut result = Vec::new();
+    let mut paragraph: Vec<Line> = Vec::new();
+    let mut in_code_fence = false;
+    let mut after_data_end = false;
+
+    for line in split_lines(tokens) {
+        after_data_end |= line.contains(&ConcreteLineToken::DataEnd);
+        let text = comment_text(&line);
+        let is_fence = text
+            .map(|(_, text)| text.starts_with("```") || text.starts_with("~~~"))
+            .unwrap_or(false);
+        if is_fence {
+            in_code_fence = !in_code_fence;
+        }
+
+        let prose = match text {
+            Some((prefix, text)) if !after_data_end && !in_code_fence && !is_fence => {
+                is_prose(text).then_some(prefix)
+            }
+            _ => None,
+        };
+        let continues_paragraph = match (prose, paragraph.first().and_then(|l| comment_text(l))) {
+            (Some(prefix), Some((paragraph_prefix, _))) => prefix == paragraph_prefix,
+            _ => false,
+        };
+
+        if !continues_paragraph {
+            result.extend(reflow_paragraph(std::mem::take(&mut paragraph)));
+        }
+        if prose.is_some() {
+            paragraph.push(line);
+        } else {
+            result.push(line);
+        }
+    }
+    result.extend(reflow_paragraph(paragraph));
+
+    join_lines(result)
+}
+
+// The indentation plus `# `, and the text after it, for lines that are
+// only a comment
+fn comment_text(line: &[ConcreteLineToken]) -> Option<(&str, &str)> {
+    match line {
+        [ConcreteLineToken::Comment { contents }] => {
+            let indent = contents.len() - contents.trim_start().len();
+            let text = contents[indent..].strip_prefix("# ")?;
+            Some((&contents[..indent + 2], text))
+        }
+        _ => None,
+    }
+}
+
+fn is_prose(text: &str) -> bool {
+    let starts_list = text.starts_with("- ")
+        || text.starts_with("* ")
+        || text.starts_with("+ ")
+        || text
+            .split_once([' ', '\t'])
+            .map(|(marker, _)| {
+                let number = marker
+                    .strip_suffix('.')
+                    .or_else(|| marker.strip_suffix(')'));
+                number
+                    .map(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+                    .unwrap_or(false)
+            })
+            .unwrap_or(false);
+
+    !text.is_empty()
+        // Code examples and the continuation lines of tags and list items
+        && !text.starts_with(char::is_whitespace)
+        // YARD tags, tables, and headings
+        && !text.starts_with(['@', '|', '=', '#'])
+        && !starts_list
+        && !text.contains("rubocop:")
+        && !text.contains("rubyfmt:")
+        && !is_magic_comment_text(text)
+}
+
+fn reflow_paragraph(paragraph: Vec<Line>) -> Vec<Line> {
+    let too_long = paragraph
+        .iter()
+        .any(|line| line.iter().map(|t| t.len()).sum::<usize>() > MAX_LINE_LENGTH);
+    if !too_long {
+        return paragraph;
+    }
+
+    let (prefix, _) = comment_text(&paragraph[0]).expect("paragraphs are made of comments");
+    let prefix = prefix.to_string();
+    let width = MAX_LINE_LENGTH.saturating_sub(prefix.len());
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for line in &paragraph {
+        let (_, text) = comment_text(line).expect("paragraphs are made of comments");
+        for word in text.split_whitespace() {
+            // A word that's longer than the width goes on a line of its own
+            if !current.is_empty() && current.len() + 1 + word.len() > width {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+    }
+    lines.push(current);
+
+    lines
+        .into_iter()
+        .map(|text| {
+            vec![ConcreteLineToken::Comment {
+                contents: format!("{}{}", prefix, text),
+            }]
+        })
+        .collect()
+}
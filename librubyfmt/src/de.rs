@@ -4,7 +4,8 @@ use serde::de::{self, Error as _};
 use std::mem::size_of;
 
 pub fn from_value<T: de::DeserializeOwned>(v: RipperTree) -> Result<T> {
-    T::deserialize(Deserializer(v.into_value()))
+    // `v` keeps the whole tree alive until we're done walking it
+    T::deserialize(Deserializer(v.value()))
 }
 
 #[derive(Clone, Copy)]
@@ -56,9 +57,19 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
         }
     }
 
+    // `nil` is the only value that stands in for `()`, e.g. for enum variants
+    // without fields
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if unsafe { ruby::rubyfmt_rb_nil_p(self.0) != 0 } {
+            visitor.visit_unit()
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
     serde::forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf unit unit_struct seq tuple
+        bytes byte_buf unit_struct seq tuple
         tuple_struct map struct enum identifier ignored_any
     }
 }
@@ -1,3 +1,4 @@
+use crate::format_options::TrailingCommas;
 use crate::line_tokens::ConcreteLineToken;
 
 #[derive(Debug, Clone)]
@@ -12,10 +13,20 @@ impl DelimiterPair {
     }
 }
 
+// What sits between the delimiters, which decides whether the last item can
+// have a trailing comma when the list is broken across lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListKind {
+    Literal,
+    Arguments,
+    Other,
+}
+
 #[derive(Debug, Clone)]
 pub struct BreakableDelims {
     single_line: DelimiterPair,
     multi_line: DelimiterPair,
+    list_kind: ListKind,
 }
 
 impl BreakableDelims {
@@ -23,6 +34,7 @@ impl BreakableDelims {
         BreakableDelims {
             single_line: DelimiterPair::new("(".to_string(), ")".to_string()),
             multi_line: DelimiterPair::new("(".to_string(), ")".to_string()),
+            list_kind: ListKind::Arguments,
         }
     }
 
@@ -30,6 +42,7 @@ impl BreakableDelims {
         BreakableDelims {
             single_line: DelimiterPair::new(" ".to_string(), "".to_string()),
             multi_line: DelimiterPair::new(" [".to_string(), "]".to_string()),
+            list_kind: ListKind::Literal,
         }
     }
 
@@ -37,6 +50,7 @@ impl BreakableDelims {
         BreakableDelims {
             single_line: DelimiterPair::new(" ".to_string(), "".to_string()),
             multi_line: DelimiterPair::new("(".to_string(), ")".to_string()),
+            list_kind: ListKind::Arguments,
         }
     }
 
@@ -44,6 +58,7 @@ impl BreakableDelims {
         BreakableDelims {
             single_line: DelimiterPair::new(" |".to_string(), "|".to_string()),
             multi_line: DelimiterPair::new(" |".to_string(), "|".to_string()),
+            list_kind: ListKind::Other,
         }
     }
 
@@ -51,6 +66,7 @@ impl BreakableDelims {
         BreakableDelims {
             single_line: DelimiterPair::new("[".to_string(), "]".to_string()),
             multi_line: DelimiterPair::new("[".to_string(), "]".to_string()),
+            list_kind: ListKind::Literal,
         }
     }
 
@@ -58,6 +74,7 @@ impl BreakableDelims {
         BreakableDelims {
             single_line: DelimiterPair::new(" ".to_string(), "".to_string()),
             multi_line: DelimiterPair::new("".to_string(), "".to_string()),
+            list_kind: ListKind::Other,
         }
     }
 
@@ -65,6 +82,7 @@ impl BreakableDelims {
         BreakableDelims {
             single_line: DelimiterPair::new("{".to_string(), "}".to_string()),
             multi_line: DelimiterPair::new("{".to_string(), "}".to_string()),
+            list_kind: ListKind::Literal,
         }
     }
 
@@ -72,6 +90,7 @@ impl BreakableDelims {
         BreakableDelims {
             single_line: DelimiterPair::new("{".to_string(), " }".to_string()),
             multi_line: DelimiterPair::new("{".to_string(), "}".to_string()),
+            list_kind: ListKind::Other,
         }
     }
 
@@ -79,6 +98,15 @@ impl BreakableDelims {
         BreakableDelims {
             single_line: DelimiterPair::new("".to_string(), "".to_string()),
             multi_line: DelimiterPair::new("".to_string(), "".to_string()),
+            list_kind: ListKind::Other,
+        }
+    }
+
+    pub fn accepts_trailing_comma(&self, style: TrailingCommas) -> bool {
+        match style {
+            TrailingCommas::Never => false,
+            TrailingCommas::Always => self.list_kind != ListKind::Other,
+            TrailingCommas::OnlyLiterals => self.list_kind == ListKind::Literal,
         }
     }
 
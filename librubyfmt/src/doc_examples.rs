@@ -0,0 +1,214 @@
+use crate::format_options::{FormatOptions, MagicCommentOptions};
+use crate::line_tokens::{join_lines, split_lines, ConcreteLineToken, Line};
+
+/// Formats the code in comments, which is the indented lines after a YARD
+/// `@example` tag, and RDoc verbatim sections (indented lines that start a
+/// paragraph, unless they continue a list item or tag). Code that doesn't
+/// parse is left as it is.
+pub fn format_doc_examples(
+    tokens: Vec<ConcreteLineToken>,
+    options: &FormatOptions,
+) -> Vec<ConcreteLineToken> {
+    let lines = split_lines(tokens);
+    let mut result = Vec::with_capacity(lines.len());
+    let mut in_code_fence = false;
+    let mut after_data_end = false;
+    let mut starts_paragraph = true;
+    // Whether the prose so far is part of a list item or a tag like
+    // `@param`, whose indented paragraphs are more of the same prose
+    let mut in_list_item = false;
+
+    let mut index = 0;
+    while index < lines.len() {
+        let line = &lines[index];
+        after_data_end |= line.contains(&ConcreteLineToken::DataEnd);
+        let (indent, body) = match comment_parts(line) {
+            Some(parts) if !after_data_end => parts,
+            _ => {
+                result.push(line.clone());
+                starts_paragraph = true;
+                in_list_item = false;
+                index += 1;
+                continue;
+            }
+        };
+
+        let text = body.trim_start();
+        let body_indent = body.len() - text.len();
+        let is_fence = text.starts_with("```") || text.starts_with("~~~");
+        if is_fence {
+            in_code_fence = !in_code_fence;
+        }
+
+        // The code after `@example` is indented past the tag, a verbatim
+        // section is indented at least two spaces past the usual one
+        let code_start = if in_code_fence || is_fence {
+            None
+        } else if text.starts_with("@example") {
+            result.push(line.clone());
+            index += 1;
+            Some(body_indent)
+        } else if starts_paragraph && body_indent > 2 && !in_list_item {
+            Some(2)
+        } else {
+            None
+        };
+
+        match code_start {
+            Some(min_indent) => {
+                let end = code_end(&lines, index, indent, min_indent);
+                let code = &lines[index..end];
+                match format_code(code, indent, options) {
+                    Some(formatted) => result.extend(formatted),
+                    None => result.extend_from_slice(code),
+                }
+                starts_paragraph = false;
+                in_list_item = false;
+                index = end;
+            }
+            None => {
+                result.push(line.clone());
+                starts_paragraph = text.is_empty();
+                if is_list_item_or_tag(text) {
+                    in_list_item = true;
+                } else if !text.is_empty() && body_indent <= 1 {
+                    in_list_item = false;
+                }
+                index += 1;
+            }
+        }
+    }
+
+    join_lines(result)
+}
+
+// Bullets, numbered items, RDoc labels and YARD/RDoc tags
+fn is_list_item_or_tag(text: &str) -> bool {
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = &text[digits..];
+    text.starts_with("- ")
+        || text.starts_with("* ")
+        || text.starts_with("+ ")
+        || (digits > 0 && (rest.starts_with(". ") || rest.starts_with(") ")))
+        || (text.starts_with('[') && text.contains("] "))
+        || matches!(text.split_whitespace().next(), Some(word) if word.ends_with("::"))
+        || matches!(
+            text.strip_prefix('@'),
+            Some(tag) if tag.starts_with(|c: char| c.is_ascii_alphabetic())
+        )
+}
+
+// The indentation before the `#`, and everything after it, for lines that are
+// only a comment
+fn comment_parts(line: &[ConcreteLineToken]) -> Option<(&str, &str)> {
+    match line {
+        [ConcreteLineToken::Comment { contents }] => {
+            let indent = contents.len() - contents.trim_start().len();
+            let body = contents[indent..].strip_prefix('#')?;
+            Some((&contents[..indent], body))
+        }
+        _ => None,
+    }
+}
+
+// Where a run of comment lines indented past `min_indent` ends, not counting
+// blank lines at the end
+fn code_end(lines: &[Line], start: usize, indent: &str, min_indent: usize) -> usize {
+    let mut end = start;
+    let mut last_code_line = start;
+    while let Some((line_indent, body)) = lines.get(end).and_then(|l| comment_parts(l)) {
+        let text = body.trim_start();
+        if line_indent != indent || (!text.is_empty() && body.len() - text.len() <= min_indent) {
+            break;
+        }
+        end += 1;
+        if !text.is_empty() {
+            last_code_line = end;
+        }
+    }
+    last_code_line
+}
+
+fn format_code(code: &[Line], indent: &str, options: &FormatOptions) -> Option<Vec<Line>> {
+    let bodies = code
+        .iter()
+        .map(|line| comment_parts(line).map(|(_, body)| body))
+        .collect::<Option<Vec<_>>>()?;
+    // Tabs can't be lined up with the rest of the comment again
+    if bodies
+        .iter()
+        .any(|body| body.trim_start_matches(' ').starts_with('\t'))
+    {
+        return None;
+    }
+    let code_indent = bodies
+        .iter()
+        .filter(|body| !body.trim().is_empty())
+        .map(|body| body.len() - body.trim_start_matches(' ').len())
+        .min()?;
+
+    // `# => result` annotations would be moved onto the line above like any
+    // other trailing comment, so they're taken off before formatting and put
+    // back on the same lines afterwards
+    let mut source = String::new();
+    let mut annotations = Vec::with_capacity(bodies.len());
+    for body in &bodies {
+        let mut annotation = None;
+        if !body.trim().is_empty() {
+            let line = &body[code_indent..];
+            let code = match line.find(" # =>") {
+                Some(at) => {
+                    annotation = Some(line[at..].trim_start());
+                    line[..at].trim_end()
+                }
+                None => line,
+            };
+            source.push_str(code);
+        }
+        annotations.push(annotation);
+        source.push('\n');
+    }
+
+    let options = FormatOptions {
+        magic_comments: MagicCommentOptions::default(),
+        ..options.clone()
+    };
+    let formatted = crate::format_buffer_with_options(&source, &options).ok()?;
+    let mut formatted_lines = formatted
+        .trim_end_matches('\n')
+        .split('\n')
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    // Once lines have been joined or split there's no telling which line an
+    // annotation belongs on, so the example is left as it is
+    if annotations.iter().any(Option::is_some) {
+        if formatted_lines.len() != annotations.len() {
+            return None;
+        }
+        for (line, annotation) in formatted_lines.iter_mut().zip(annotations) {
+            if let Some(annotation) = annotation {
+                if line.is_empty() {
+                    return None;
+                }
+                line.push(' ');
+                line.push_str(annotation);
+            }
+        }
+    }
+
+    let prefix = format!("{}#{}", indent, " ".repeat(code_indent));
+    Some(
+        formatted_lines
+            .into_iter()
+            .map(|line| {
+                let contents = if line.is_empty() {
+                    format!("{}#", indent)
+                } else {
+                    format!("{}{}", prefix, line)
+                };
+                vec![ConcreteLineToken::Comment { contents }]
+            })
+            .collect(),
+    )
+}
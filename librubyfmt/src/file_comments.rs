@@ -4,7 +4,7 @@ use std::mem;
 use log::debug;
 
 use crate::comment_block::CommentBlock;
-use crate::parser_state::line_difference_requires_newline;
+use crate::parser_state::blank_lines_between;
 use crate::ruby::*;
 use crate::types::LineNumber;
 
@@ -19,13 +19,21 @@ pub struct FileComments {
 impl FileComments {
     pub fn from_ruby_hash(h: VALUE, rl: VALUE, last_lineno: VALUE) -> Self {
         let mut fc = FileComments::default();
+        // These arrays are only referenced from Rust, so they need guarding
+        // against the GC while we read from them
+        let keys_guard;
+        let values_guard;
+        let lines_guard;
         let keys;
         let values;
         let lines;
         unsafe {
-            keys = ruby_array_to_slice(rb_funcall(h, intern!("keys"), 0));
-            values = ruby_array_to_slice(rb_funcall(h, intern!("values"), 0));
-            lines = ruby_array_to_slice(rb_funcall(rl, intern!("keys"), 0));
+            keys_guard = GcGuard::new(rb_funcall(h, intern!("keys"), 0));
+            values_guard = GcGuard::new(rb_funcall(h, intern!("values"), 0));
+            lines_guard = GcGuard::new(rb_funcall(rl, intern!("keys"), 0));
+            keys = ruby_array_to_slice(keys_guard.value());
+            values = ruby_array_to_slice(values_guard.value());
+            lines = ruby_array_to_slice(lines_guard.value());
             fc.last_lineno = rubyfmt_rb_num2ll(last_lineno) as LineNumber;
         }
         if keys.len() != values.len() {
@@ -105,6 +113,7 @@ impl FileComments {
         &mut self,
         starting_line_number: LineNumber,
         line_number: LineNumber,
+        max_blank_lines: LineNumber,
     ) -> Option<(CommentBlock, LineNumber)> {
         self.other_comments
             .keys()
@@ -125,26 +134,31 @@ impl FileComments {
                 let mut comment_block_with_spaces: Vec<String> = Vec::new();
                 let mut last_line = None;
 
-                if line_difference_requires_newline(
+                for _ in 0..blank_lines_between(
                     comments.first().unwrap().0,
                     starting_line_number,
+                    max_blank_lines,
                 ) {
                     comment_block_with_spaces.push(String::new());
                 }
 
                 for (index, comment_contents) in comments {
-                    if last_line.is_some()
-                        && line_difference_requires_newline(index, last_line.unwrap())
-                    {
-                        comment_block_with_spaces.push(String::new());
+                    if let Some(last_line) = last_line {
+                        for _ in 0..blank_lines_between(index, last_line, max_blank_lines) {
+                            comment_block_with_spaces.push(String::new());
+                        }
                     }
                     last_line = Some(index);
                     comment_block_with_spaces.push(comment_contents);
                 }
 
                 if line_number > last_line.unwrap() + 1 {
+                    for _ in
+                        0..blank_lines_between(line_number, last_line.unwrap(), max_blank_lines)
+                    {
+                        comment_block_with_spaces.push(String::new());
+                    }
                     last_line = Some(line_number);
-                    comment_block_with_spaces.push(String::new());
                 }
 
                 (
@@ -1,8 +1,17 @@
 use std::collections::HashSet;
 
+use crate::access_modifiers::{
+    access_modifiers_to_inline, access_modifiers_to_sections, bare_access_modifier,
+};
 use crate::delimiters::BreakableDelims;
+use crate::format_options::{
+    AccessModifierStyle, BlockDelimiterStyle, BooleanOperatorStyle, DotPosition, HashKeyStyle,
+    QuoteStyle,
+};
 use crate::heredoc_string::HeredocKind;
+use crate::numeric_literals::normalize_numeric_literal;
 use crate::parser_state::{BaseParserState, ConcreteParserState, FormattingContext, RenderFunc};
+use crate::render_queue_writer::MAX_LINE_LENGTH;
 use crate::ripper_tree_types::*;
 use crate::types::LineNumber;
 use log::debug;
@@ -251,8 +260,10 @@ pub fn format_block_arg(
                 Box::new(|ps| {
                     ps.emit_soft_indent();
                     ps.emit_ident("&".to_string());
-                    bind_ident(ps, &ba.1);
-                    format_ident(ps, ba.1);
+                    if let Some(ident) = ba.1 {
+                        bind_ident(ps, &ident);
+                        format_ident(ps, ident);
+                    }
                 }),
             );
 
@@ -480,8 +491,12 @@ pub fn format_bodystmt(
     let else_part = bodystmt.3;
     let ensure_part = bodystmt.4;
 
-    for expression in expressions {
-        format_expression(ps, expression);
+    if ps.current_formatting_context() == FormattingContext::ClassOrModule {
+        format_class_body_expressions(ps, expressions);
+    } else {
+        for expression in expressions {
+            format_expression(ps, expression);
+        }
     }
 
     // Else statements are actually just an array of statements in many cases,
@@ -695,6 +710,35 @@ pub fn args_has_single_def_expression(args: &ArgsAddStarOrExpressionListOrArgsFo
     false
 }
 
+fn args_end_with_omitted_hash_value(args: &ArgsAddStarOrExpressionListOrArgsForward) -> bool {
+    let last_arg = match args {
+        ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(el) => el.last(),
+        ArgsAddStarOrExpressionListOrArgsForward::ArgsAddStar(aas) => aas.3.last(),
+        ArgsAddStarOrExpressionListOrArgsForward::ArgsForward(_) => None,
+    };
+
+    match last_arg {
+        Some(Expression::BareAssocHash(BareAssocHash(_, assocs))) => matches!(
+            assocs.last(),
+            Some(AssocNewOrAssocSplat::AssocNew(new)) if new.2.is_none()
+        ),
+        _ => false,
+    }
+}
+
+/// Whether dropping the parens from a call with these args would still parse
+/// the same way, e.g. `foo({ a: 1 })` can't become `foo { a: 1 }` since the
+/// braces would be read as a block
+fn args_can_omit_parens(args: &ArgsAddStarOrExpressionListOrArgsForward) -> bool {
+    match args {
+        ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(el) => {
+            !matches!(el.first(), Some(Expression::Hash(..)))
+        }
+        ArgsAddStarOrExpressionListOrArgsForward::ArgsAddStar(aas) => args_can_omit_parens(&aas.1),
+        ArgsAddStarOrExpressionListOrArgsForward::ArgsForward(_) => false,
+    }
+}
+
 lazy_static! {
     static ref RSPEC_METHODS: HashSet<&'static str> = vec!["it", "describe"].into_iter().collect();
     static ref GEMFILE_METHODS: HashSet<&'static str> = vec![
@@ -717,6 +761,7 @@ pub fn use_parens_for_method_call(
     args: &ArgsAddStarOrExpressionListOrArgsForward,
     original_used_parens: bool,
     context: FormattingContext,
+    is_statement: bool,
 ) -> bool {
     let name = method.get_name();
     debug!("name: {:?}", name);
@@ -732,11 +777,19 @@ pub fn use_parens_for_method_call(
     if matches!(method, IdentOrOpOrKeywordOrConst::Const(..)) {
         return true;
     }
+    // Without parens, a trailing shorthand hash value (`foo x:`) would
+    // swallow whatever comes on the next line as its value
+    if args_end_with_omitted_hash_value(args) {
+        return true;
+    }
     if name.starts_with("attr_") && context == FormattingContext::ClassOrModule {
         return original_used_parens;
     }
 
-    if ps.scope_has_variable(&name) {
+    // `it` is only the implicit block param when it's used bare, with
+    // arguments it's always a method call, e.g. rspec's `it "does things"`
+    let is_it_with_args = name == "it" && !args.is_empty();
+    if ps.scope_has_variable(&name) && !is_it_with_args {
         match chain.first() {
             None => return original_used_parens,
             Some(CallChainElement::VarRef(VarRef(_, VarRefType::Kw(Kw(_, x, _))))) => {
@@ -765,10 +818,19 @@ pub fn use_parens_for_method_call(
 
     if OPTIONALLY_PARENTHESIZED_METHODS.contains(name.as_str())
         || GEMFILE_METHODS.contains(name.as_str())
+        || ps.format_options().preserve_parens_methods.contains(&name)
     {
         return original_used_parens;
     }
 
+    if is_statement
+        && ps.format_options().omit_parens_methods.contains(&name)
+        && !args.is_empty()
+        && args_can_omit_parens(args)
+    {
+        return false;
+    }
+
     if args.is_empty() {
         return false;
     } else {
@@ -826,7 +888,8 @@ pub fn format_dot(ps: &mut dyn ConcreteParserState, dot: DotTypeOrOp) {
 }
 
 pub fn format_method_call(ps: &mut dyn ConcreteParserState, method_call: MethodCall) {
-    if ps.at_start_of_line() {
+    let is_statement = ps.at_start_of_line();
+    if is_statement {
         ps.emit_indent();
     }
 
@@ -840,6 +903,7 @@ pub fn format_method_call(ps: &mut dyn ConcreteParserState, method_call: MethodC
         &args,
         original_used_parens,
         ps.current_formatting_context(),
+        is_statement,
     );
     chain.extend([
         CallChainElement::IdentOrOpOrKeywordOrConst(method),
@@ -870,12 +934,25 @@ pub fn format_list_like_thing_items(
     args: Vec<Expression>,
     end_line: Option<LineNumber>,
     single_line: bool,
+) -> bool {
+    format_list_items(ps, args, end_line, single_line, true)
+}
+
+// `ends_list` is false when more items (e.g. a splat) come after these ones,
+// in which case the last of them can't take a trailing comma
+fn format_list_items(
+    ps: &mut dyn ConcreteParserState,
+    args: Vec<Expression>,
+    end_line: Option<LineNumber>,
+    single_line: bool,
+    ends_list: bool,
 ) -> bool {
     let mut emitted_args = false;
     let skip_magic_comments = args
         .iter()
         .any(|i| matches!(i, Expression::StringConcat(..)));
     let args_count = args.len();
+    let last_arg_takes_trailing_comma = args.last().map(can_take_trailing_comma);
     let cls: RenderFunc = Box::new(|ps| {
         for (idx, expr) in args.into_iter().enumerate() {
             if single_line {
@@ -905,6 +982,9 @@ pub fn format_list_like_thing_items(
                             ps.emit_comma();
                             ps.emit_soft_newline();
                         } else {
+                            if ends_list && last_arg_takes_trailing_comma == Some(true) {
+                                ps.emit_trailing_comma();
+                            }
                             ps.shift_comments();
                         }
                     }),
@@ -951,13 +1031,21 @@ pub fn format_int(ps: &mut dyn ConcreteParserState, int: Int) {
         ps.emit_indent();
     }
 
-    handle_string_and_linecol(ps, int.1, int.2);
+    format_numeric_literal(ps, int.1, int.2);
 
     if ps.at_start_of_line() {
         ps.emit_newline();
     }
 }
 
+fn format_numeric_literal(ps: &mut dyn ConcreteParserState, literal: String, lc: LineCol) {
+    let literal = match ps.format_options().numeric_literals {
+        Some(style) => normalize_numeric_literal(&literal, style),
+        None => literal,
+    };
+    handle_string_and_linecol(ps, literal, lc);
+}
+
 pub fn format_bare_assoc_hash(ps: &mut dyn ConcreteParserState, bah: BareAssocHash) {
     format_assocs(ps, bah.1, SpecialCase::NoSpecialCase)
 }
@@ -1073,28 +1161,106 @@ pub fn format_symbol_literal(ps: &mut dyn ConcreteParserState, symbol_literal: S
     }
 }
 
-fn all_labelish(assocs: &[AssocNewOrAssocSplat]) -> bool {
+fn all_labelish(ps: &dyn ConcreteParserState, assocs: &[AssocNewOrAssocSplat]) -> bool {
+    let prefer_labels = ps.format_options().hash_key_style == HashKeyStyle::PreferLabels;
     assocs.iter().all(|assoc| match assoc {
-        AssocNewOrAssocSplat::AssocNew(new) => match new.1 {
+        AssocNewOrAssocSplat::AssocNew(new) => match &new.1 {
             AssocKey::Label(_) => true,
-            AssocKey::Expression(_) => false,
+            AssocKey::Expression(expr) => prefer_labels && symbol_key_label(expr).is_some(),
         },
         AssocNewOrAssocSplat::AssocSplat(_) => true,
     })
 }
 
+enum SymbolKeyLabel {
+    /// e.g. `foo:` for `:foo`
+    Bare(String, LineCol),
+    /// e.g. `"foo-bar":` for `:"foo-bar"`
+    Quoted(StringLiteral),
+}
+
+/// How a symbol hash key can be written as a label, `None` for keys that
+/// aren't symbols or whose name can't go inside of quotes as is.
+fn symbol_key_label(expr: &Expression) -> Option<SymbolKeyLabel> {
+    match expr {
+        Expression::SymbolLiteral(SymbolLiteral(_, SymbolOrBare::Symbol(Symbol(_, name)), _)) => {
+            let (name, lc) = match name {
+                IdentOrConstOrKwOrOpOrIvarOrGvarOrCvarOrBacktick::Ident(Ident(_, name, lc))
+                | IdentOrConstOrKwOrOpOrIvarOrGvarOrCvarOrBacktick::Const(Const(_, name, lc))
+                | IdentOrConstOrKwOrOpOrIvarOrGvarOrCvarOrBacktick::Keyword(Kw(_, name, lc))
+                | IdentOrConstOrKwOrOpOrIvarOrGvarOrCvarOrBacktick::IVar(IVar(_, name, lc))
+                | IdentOrConstOrKwOrOpOrIvarOrGvarOrCvarOrBacktick::GVar(GVar(_, name, lc))
+                | IdentOrConstOrKwOrOpOrIvarOrGvarOrCvarOrBacktick::CVar(CVar(_, name, lc)) => {
+                    (name.clone(), lc)
+                }
+                IdentOrConstOrKwOrOpOrIvarOrGvarOrCvarOrBacktick::Backtick(backtick) => {
+                    (backtick.1.clone(), &backtick.2)
+                }
+                IdentOrConstOrKwOrOpOrIvarOrGvarOrCvarOrBacktick::Op(Op(_, op, lc, _)) => {
+                    match op {
+                        Operator::Equals(_) => ("==".to_string(), lc),
+                        Operator::StringOperator(name) => (name.clone(), lc),
+                        Operator::Dot(_) | Operator::LonelyOperator(_) => return None,
+                    }
+                }
+            };
+
+            // Globals like `:$"` would need escaping inside of the quotes
+            if name.contains(['"', '\\', '#']) {
+                return None;
+            }
+
+            if is_bare_label_name(&name) {
+                Some(SymbolKeyLabel::Bare(name, lc.clone()))
+            } else {
+                // Setter names like `:foo=` and operators like `:+` need
+                // quotes, `foo=: 1` and `+: 1` aren't valid
+                Some(SymbolKeyLabel::Quoted(StringLiteral::Normal(
+                    string_literal_tag,
+                    StringContent(
+                        string_content_tag,
+                        vec![StringContentPart::TStringContent(TStringContent(
+                            tstring_content_tag,
+                            name,
+                            lc.clone(),
+                        ))],
+                    ),
+                    None,
+                    StartEnd(lc.0, lc.0),
+                )))
+            }
+        }
+        Expression::DynaSymbol(ds) => Some(SymbolKeyLabel::Quoted(ds.clone().to_string_literal())),
+        _ => None,
+    }
+}
+
+fn is_bare_label_name(name: &str) -> bool {
+    let name = name
+        .strip_suffix('?')
+        .or_else(|| name.strip_suffix('!'))
+        .unwrap_or(name);
+    let is_ident_char = |c: char| c == '_' || c.is_alphanumeric() || !c.is_ascii();
+
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if is_ident_char(c) && !c.is_ascii_digit())
+        && chars.all(is_ident_char)
+}
+
 pub fn format_assocs(
     ps: &mut dyn ConcreteParserState,
     assocs: Vec<AssocNewOrAssocSplat>,
     sc: SpecialCase,
 ) {
     let len = assocs.len();
-    let all_labelish = all_labelish(&assocs);
+    let all_labelish = all_labelish(ps, &assocs);
     for (idx, assoc) in assocs.into_iter().enumerate() {
         ps.emit_soft_indent();
         format_assoc(ps, assoc, all_labelish);
         if idx != len - 1 {
             ps.emit_comma();
+        } else if sc == SpecialCase::NoSpecialCase {
+            ps.emit_trailing_comma();
         }
         if !(idx == len - 1 && sc == SpecialCase::NoLeadingTrailingCollectionMarkers) {
             ps.emit_soft_newline();
@@ -1107,7 +1273,7 @@ pub fn format_assocs_single_line(
     assocs: Vec<AssocNewOrAssocSplat>,
 ) {
     let len = assocs.len();
-    let all_labelish = all_labelish(&assocs);
+    let all_labelish = all_labelish(ps, &assocs);
     for (idx, assoc) in assocs.into_iter().enumerate() {
         format_assoc(ps, assoc, all_labelish);
         if idx != len - 1 {
@@ -1127,7 +1293,9 @@ pub fn format_assoc(
             AssocNewOrAssocSplat::AssocNew(new) => {
                 match new.1 {
                     AssocKey::Label(label) => {
-                        if all_labelish {
+                        // Shorthand hash values (`{x:}`) only work with labels,
+                        // so leave those alone even if the rest of the hash uses rockets
+                        if all_labelish || new.2.is_none() {
                             handle_string_and_linecol(ps, label.1, label.2);
                         } else {
                             let colonless_label = label
@@ -1141,9 +1309,25 @@ pub fn format_assoc(
                         }
                     }
                     AssocKey::Expression(expression) => {
-                        format_expression(ps, expression);
-                        ps.emit_space();
-                        ps.emit_ident("=>".to_string());
+                        let label = if all_labelish {
+                            symbol_key_label(&expression)
+                        } else {
+                            None
+                        };
+                        match label {
+                            Some(SymbolKeyLabel::Bare(name, lc)) => {
+                                handle_string_and_linecol(ps, format!("{}:", name), lc);
+                            }
+                            Some(SymbolKeyLabel::Quoted(string_literal)) => {
+                                format_string_literal(ps, string_literal);
+                                ps.emit_ident(":".to_string());
+                            }
+                            None => {
+                                format_expression(ps, expression);
+                                ps.emit_space();
+                                ps.emit_ident("=>".to_string());
+                            }
+                        }
                     }
                 }
                 if let Some(expr) = new.2 {
@@ -1153,7 +1337,9 @@ pub fn format_assoc(
             }
             AssocNewOrAssocSplat::AssocSplat(splat) => {
                 ps.emit_ident("**".to_string());
-                format_expression(ps, splat.1);
+                if let Some(expr) = splat.1 {
+                    format_expression(ps, expr);
+                }
             }
         }),
     );
@@ -1457,13 +1643,23 @@ pub fn format_list_like_thing(
     a: ArgsAddStarOrExpressionListOrArgsForward,
     end_line: Option<LineNumber>,
     single_line: bool,
+) -> bool {
+    format_list(ps, a, end_line, single_line, true)
+}
+
+fn format_list(
+    ps: &mut dyn ConcreteParserState,
+    a: ArgsAddStarOrExpressionListOrArgsForward,
+    end_line: Option<LineNumber>,
+    single_line: bool,
+    ends_list: bool,
 ) -> bool {
     match a {
         ArgsAddStarOrExpressionListOrArgsForward::ArgsAddStar(aas) => {
             let left = aas.1;
             let star = aas.2;
             let right = aas.3;
-            let mut emitted_args = format_list_like_thing(ps, *left, None, single_line);
+            let mut emitted_args = format_list(ps, *left, None, single_line, false);
 
             if single_line {
                 // if we're single line, our predecessor didn't emit a trailing comma
@@ -1484,12 +1680,16 @@ pub fn format_list_like_thing(
             }
 
             emitted_args = true;
+            let takes_trailing_comma =
+                ends_list && right.last().map_or(true, can_take_trailing_comma);
 
             ps.with_start_of_line(
                 false,
                 Box::new(|ps| {
                     ps.emit_ident("*".to_string());
-                    format_expression(ps, *star);
+                    if let Some(star) = star {
+                        format_expression(ps, *star);
+                    }
 
                     for expr in right {
                         match expr {
@@ -1508,6 +1708,9 @@ pub fn format_list_like_thing(
                             }
                         }
                     }
+                    if !single_line && takes_trailing_comma {
+                        ps.emit_trailing_comma();
+                    }
                     if let Some(end_line) = end_line {
                         ps.wind_dumping_comments_until_line(end_line);
                         ps.shift_comments();
@@ -1518,7 +1721,7 @@ pub fn format_list_like_thing(
             emitted_args
         }
         ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(el) => {
-            format_list_like_thing_items(ps, el, end_line, single_line)
+            format_list_items(ps, el, end_line, single_line, ends_list)
         }
         ArgsAddStarOrExpressionListOrArgsForward::ArgsForward(_) => {
             ps.emit_ellipsis();
@@ -1527,6 +1730,11 @@ pub fn format_list_like_thing(
     }
 }
 
+// `foo(a, &blk,)` isn't valid ruby
+fn can_take_trailing_comma(expr: &Expression) -> bool {
+    !matches!(expr, Expression::ToProc(..))
+}
+
 pub fn emit_intermediate_array_separator(ps: &mut dyn ConcreteParserState, single_line: bool) {
     if single_line {
         ps.emit_comma_space();
@@ -1659,16 +1867,36 @@ pub fn format_string_literal(ps: &mut dyn ConcreteParserState, sl: StringLiteral
         StringLiteral::Heredoc(_, hd, StringContent(_, parts)) => {
             format_heredoc_string_literal(ps, hd, parts)
         }
-        StringLiteral::Normal(_, StringContent(_, parts), start_end) => {
+        StringLiteral::Normal(_, StringContent(_, parts), delim, start_end) => {
             if ps.at_start_of_line() {
                 ps.emit_indent();
             }
 
             ps.on_line(start_end.start_line());
 
-            ps.emit_double_quote();
-            format_inner_string(ps, parts, StringType::Quoted);
-            ps.emit_double_quote();
+            let was_single_quoted =
+                matches!(delim.as_deref(), Some(d) if d == "'" || d.starts_with("%q"));
+            let single_quoted_contents = match ps.format_options().quote_style {
+                QuoteStyle::Double => None,
+                QuoteStyle::Single => single_quoted_string_contents(&parts, false),
+                QuoteStyle::Preserve if was_single_quoted => {
+                    single_quoted_string_contents(&parts, true)
+                }
+                QuoteStyle::Preserve => None,
+            };
+
+            match single_quoted_contents {
+                Some(contents) => {
+                    ps.emit_single_quote();
+                    ps.emit_string_content(contents);
+                    ps.emit_single_quote();
+                }
+                None => {
+                    ps.emit_double_quote();
+                    format_inner_string(ps, parts, StringType::Quoted);
+                    ps.emit_double_quote();
+                }
+            }
 
             if ps.at_start_of_line() {
                 ps.emit_newline();
@@ -1677,6 +1905,178 @@ pub fn format_string_literal(ps: &mut dyn ConcreteParserState, sl: StringLiteral
     }
 }
 
+/// Turns a string literal that would run past the end of the line into a
+/// `"..." \\` concatenation when `split_long_strings` is on, or returns `None`
+/// if it fits or can't be split. Strings are only split after a space, and
+/// only when they have no interpolation, so a piece never ends inside of an
+/// escape or `#{}`.
+fn split_long_string_literal(
+    ps: &dyn ConcreteParserState,
+    sl: &StringLiteral,
+) -> Option<StringConcat> {
+    if !ps.format_options().split_long_strings
+        || ps.current_formatting_context() == FormattingContext::StringEmbexpr
+    {
+        return None;
+    }
+    let (content, delim, start_end) = match sl {
+        StringLiteral::Normal(_, StringContent(_, parts), delim, start_end) => {
+            match parts.as_slice() {
+                [StringContentPart::TStringContent(content)] => (content, delim, start_end),
+                _ => return None,
+            }
+        }
+        StringLiteral::Heredoc(..) => return None,
+    };
+    if content.1.contains('\n') {
+        return None;
+    }
+
+    let start = if ps.at_start_of_line() {
+        ps.current_indentation()
+    } else {
+        ps.current_line_length()
+    };
+    // Each piece gets its quotes, and all but the last get a ` \`
+    let quotes_length = "\"\" \\".len();
+    if start + content.1.len() + 2 <= MAX_LINE_LENGTH {
+        return None;
+    }
+    // Pieces after the first are indented under the line the string starts on
+    let continuation_indentation = if ps.is_absorbing_indents() {
+        ps.current_indentation()
+    } else {
+        ps.current_indentation() + 2
+    };
+
+    let split_points = string_split_points(&content.1);
+    let mut pieces = Vec::new();
+    let mut piece_start = 0;
+    let mut available = MAX_LINE_LENGTH.saturating_sub(start + quotes_length);
+    while piece_start + available < content.1.len() {
+        let fitting = split_points
+            .iter()
+            .copied()
+            .filter(|point| *point > piece_start)
+            .take_while(|point| point - piece_start <= available)
+            .last();
+        let next_point = split_points
+            .iter()
+            .copied()
+            .find(|point| *point > piece_start);
+        let piece_end = match fitting.or(next_point) {
+            Some(piece_end) => piece_end,
+            None => break,
+        };
+        pieces.push(content.1[piece_start..piece_end].to_string());
+        piece_start = piece_end;
+        available = MAX_LINE_LENGTH.saturating_sub(continuation_indentation + quotes_length);
+    }
+    if pieces.is_empty() {
+        return None;
+    }
+    pieces.push(content.1[piece_start..].to_string());
+
+    let mut pieces = pieces.into_iter().map(|piece| {
+        StringLiteral::Normal(
+            string_literal_tag,
+            StringContent(
+                string_content_tag,
+                vec![StringContentPart::TStringContent(TStringContent(
+                    tstring_content_tag,
+                    piece,
+                    content.2.clone(),
+                ))],
+            ),
+            delim.clone(),
+            start_end.clone(),
+        )
+    });
+    let first = StringConcatOrStringLiteral::StringLiteral(
+        pieces.next().expect("there are at least two pieces"),
+    );
+    let concat = pieces.fold(first, |nested, piece| {
+        StringConcatOrStringLiteral::StringConcat(Box::new(StringConcat(
+            string_concat_tag,
+            nested,
+            piece,
+        )))
+    });
+    match concat {
+        StringConcatOrStringLiteral::StringConcat(sc) => Some(*sc),
+        StringConcatOrStringLiteral::StringLiteral(_) => None,
+    }
+}
+
+// The offsets right after each space in a string's contents that isn't part
+// of an escape, which are the places it can be split. A space at the very
+// end has nothing to split off.
+fn string_split_points(contents: &str) -> Vec<usize> {
+    let mut points = Vec::new();
+    let mut chars = contents.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            ' ' if index + 1 < contents.len() => points.push(index + 1),
+            '\\' => match chars.next() {
+                // `\u{1F600 1F601}` can have spaces inside of the braces
+                Some((_, 'u')) if chars.peek().map(|(_, c)| *c) == Some('{') => {
+                    for (_, c) in chars.by_ref() {
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                }
+                // `\C-x`, `\M-x`, and `\cx` take the character after them along
+                Some((_, 'C' | 'M')) if chars.next_if(|(_, c)| *c == '-').is_some() => {
+                    chars.next();
+                }
+                Some((_, 'c')) => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    points
+}
+
+/// String contents always come to us escaped for double quotes, this
+/// rewrites them for single quotes. Returns `None` if that isn't possible,
+/// i.e. the string has interpolation or escapes like `\n` that only mean
+/// something in double quotes, or contains a `'` and `allow_single_quotes`
+/// is false.
+fn single_quoted_string_contents(
+    parts: &[StringContentPart],
+    allow_single_quotes: bool,
+) -> Option<String> {
+    let mut contents = String::new();
+    for part in parts {
+        let s = match part {
+            StringContentPart::TStringContent(TStringContent(_, s, _)) => s,
+            _ => return None,
+        };
+
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('\\') => contents.push_str("\\\\"),
+                    Some('"') => contents.push('"'),
+                    Some('#') => contents.push('#'),
+                    Some('\'') if allow_single_quotes => contents.push_str("\\'"),
+                    _ => return None,
+                },
+                '\'' if allow_single_quotes => contents.push_str("\\'"),
+                '\'' => return None,
+                c => contents.push(c),
+            }
+        }
+    }
+
+    Some(contents)
+}
+
 pub fn format_xstring_literal(ps: &mut dyn ConcreteParserState, xsl: XStringLiteral) {
     let parts = xsl.1;
 
@@ -1844,7 +2244,14 @@ pub fn format_assign(ps: &mut dyn ConcreteParserState, assign: Assign) {
             ps.with_formatting_context(
                 FormattingContext::Assign,
                 Box::new(|ps| match right {
-                    ExpressionOrMRHSNewFromArgs::Expression(e) => format_expression(ps, *e),
+                    ExpressionOrMRHSNewFromArgs::Expression(e) => {
+                        match ternary_as_conditional(ps, *e) {
+                            Expression::MethodAddBlock(mab) => {
+                                format_method_add_block_with(ps, mab, true)
+                            }
+                            e => format_expression(ps, e),
+                        }
+                    }
                     ExpressionOrMRHSNewFromArgs::MRHSNewFromArgs(m) => {
                         format_mrhs_new_from_args(ps, m)
                     }
@@ -2067,7 +2474,7 @@ pub fn format_next(ps: &mut dyn ConcreteParserState, next: Next) {
                     }
                 }
                 ArgsAddBlockOrExpressionList::ArgsAddBlock(aab) => match aab.2 {
-                    ToProcExpr::Present(_) => {
+                    ToProcExpr::Present(_) | ToProcExpr::Anonymous => {
                         panic!("got a block in a next, should be impossible");
                     }
                     ToProcExpr::NotPresent(_) => {
@@ -2284,6 +2691,52 @@ fn format_constant_body(ps: &mut dyn ConcreteParserState, bodystmt: Box<BodyStmt
     }
 }
 
+// The statements directly inside of a class or module, where bare access
+// modifiers like `private` get laid out according to `access_modifiers`
+fn format_class_body_expressions(ps: &mut dyn ConcreteParserState, expressions: Vec<Expression>) {
+    let options = ps.format_options().access_modifiers;
+    let expressions = expressions.into_iter().map(normalize).collect();
+    let expressions = match options.style {
+        AccessModifierStyle::Preserve => expressions,
+        AccessModifierStyle::Inline => access_modifiers_to_inline(expressions),
+        AccessModifierStyle::Sections => access_modifiers_to_sections(expressions),
+    };
+
+    let mut expressions = expressions.into_iter().peekable();
+    while let Some(expression) = expressions.next() {
+        let (name, line) = match bare_access_modifier(&expression) {
+            Some(modifier) => modifier,
+            None => {
+                format_expression(ps, expression);
+                continue;
+            }
+        };
+
+        ps.on_line(line);
+        ps.emit_indent();
+        ps.emit_access_modifier(name);
+
+        if options.indent_sections {
+            let mut section = Vec::new();
+            while let Some(next) = expressions.next_if(|e| bare_access_modifier(e).is_none()) {
+                section.push(next);
+            }
+
+            ps.new_block(Box::new(|ps| {
+                ps.emit_newline();
+                for expression in section {
+                    format_expression(ps, expression);
+                }
+            }));
+            // Comments before the next modifier line up with it, not the
+            // section above
+            ps.reset_space_count();
+        } else {
+            ps.emit_newline();
+        }
+    }
+}
+
 pub fn format_class(ps: &mut dyn ConcreteParserState, class: Class) {
     if ps.at_start_of_line() {
         ps.emit_indent();
@@ -2483,10 +2936,17 @@ pub fn format_binary(ps: &mut dyn ConcreteParserState, binary: Binary) {
         ps.on_line(binary.2 .1.start_line());
     }
 
+    // A leading operator needs a `\\` on the line before it (before Ruby 3.5,
+    // parentheses around the condition don't help), which can't come after a
+    // comment, so those chains keep their operators trailing
+    let (first_line, last_line) = binary_line_range(&binary);
+    let leading_operators = ps.format_options().boolean_operators == BooleanOperatorStyle::Leading
+        && !ps.has_comments_in_line(first_line, last_line);
+
     ps.inline_breakable_of(
         BreakableDelims::for_binary_op(),
         Box::new(|ps| {
-            format_binary_inner(ps, binary);
+            format_binary_inner(ps, binary, leading_operators);
         }),
     );
 
@@ -2495,10 +2955,31 @@ pub fn format_binary(ps: &mut dyn ConcreteParserState, binary: Binary) {
     }
 }
 
+fn is_boolean_operator(op: &str) -> bool {
+    matches!(op, "&&" | "||" | "and" | "or")
+}
+
+fn binary_line_range(binary: &Binary) -> (LineNumber, LineNumber) {
+    let op_line = binary.2 .1.start_line();
+    let first_line = match &*binary.1 {
+        Expression::Binary(bin) => binary_line_range(bin).0,
+        expr => expr.start_line().unwrap_or(op_line),
+    };
+    let last_line = match &*binary.3 {
+        Expression::Binary(bin) => binary_line_range(bin).1,
+        expr => expr.start_line().unwrap_or(op_line),
+    };
+    (first_line, last_line)
+}
+
 // Performs the actual formatting for binary operators. This method assumes that it's
 // inside of a breakable, but it's separated out so that it can recurse inside of
 // nested breakables so that nested breakables stay at the same indentation level.
-fn format_binary_inner(ps: &mut dyn ConcreteParserState, binary: Binary) {
+//
+// Chains of `&&` and `||` only share their breakable with each other, anything
+// else inside of them (e.g. `a + b` in `a + b > c && d`) gets its own, so that
+// splitting the chain one operand per line doesn't also split the arithmetic.
+fn format_binary_inner(ps: &mut dyn ConcreteParserState, binary: Binary, leading_operators: bool) {
     ps.with_formatting_context(
         FormattingContext::Binary,
         Box::new(|ps| {
@@ -2506,14 +2987,20 @@ fn format_binary_inner(ps: &mut dyn ConcreteParserState, binary: Binary) {
                 false,
                 Box::new(|ps| {
                     let op = binary.2;
+                    let is_boolean = is_boolean_operator(&op.0);
+                    let shares_breakable =
+                        |bin: &Binary| !is_boolean || is_boolean_operator(&bin.2 .0);
                     let left_hand_side = *binary.1;
 
-                    if let Expression::Binary(bin) = left_hand_side {
-                        format_binary_inner(ps, bin);
-                    } else {
-                        ps.dedent(Box::new(|ps| {
-                            format_expression(ps, left_hand_side);
-                        }));
+                    match left_hand_side {
+                        Expression::Binary(bin) if shares_breakable(&bin) => {
+                            format_binary_inner(ps, bin, leading_operators);
+                        }
+                        left_hand_side => {
+                            ps.dedent(Box::new(|ps| {
+                                format_expression(ps, left_hand_side);
+                            }));
+                        }
                     }
 
                     let comparison_operators = vec![">", ">=", "===", "==", "<", "<=", "<=>", "!="];
@@ -2521,22 +3008,31 @@ fn format_binary_inner(ps: &mut dyn ConcreteParserState, binary: Binary) {
 
                     let next_expr = *binary.3;
 
-                    ps.emit_space();
-                    ps.emit_ident(op.0);
-                    if is_not_comparison {
+                    if is_boolean && leading_operators {
+                        ps.emit_line_continuation();
                         ps.emit_soft_newline();
                         ps.emit_soft_indent();
+                        ps.emit_ident(op.0);
+                        ps.emit_space();
                     } else {
                         ps.emit_space();
+                        ps.emit_ident(op.0);
+                        if is_not_comparison {
+                            ps.emit_soft_newline();
+                            ps.emit_soft_indent();
+                        } else {
+                            ps.emit_space();
+                        }
                     }
                     // In some cases, previous expressions changed the space
                     // count but haven't reset it, so we force a reset here in
                     // case we shift comments during the _next_ expression
                     ps.reset_space_count();
-                    if let Expression::Binary(bin) = next_expr {
-                        format_binary_inner(ps, bin);
-                    } else {
-                        format_expression(ps, next_expr);
+                    match next_expr {
+                        Expression::Binary(bin) if shares_breakable(&bin) => {
+                            format_binary_inner(ps, bin, leading_operators);
+                        }
+                        next_expr => format_expression(ps, next_expr),
                     }
                 }),
             );
@@ -2549,7 +3045,7 @@ pub fn format_float(ps: &mut dyn ConcreteParserState, float: Float) {
         ps.emit_indent();
     }
 
-    handle_string_and_linecol(ps, float.1, float.2);
+    format_numeric_literal(ps, float.1, float.2);
 
     if ps.at_start_of_line() {
         ps.emit_newline();
@@ -2679,7 +3175,10 @@ pub fn format_backref(ps: &mut dyn ConcreteParserState, backref: Backref) {
 
 /// Matches call chains on common special-cased names, like
 /// `it`/`describe` for tests and `gem`/`source`/etc. for Gemfiles.
-fn can_elide_parens_for_reserved_names(cc: &[CallChainElement]) -> bool {
+fn can_elide_parens_for_reserved_names(
+    ps: &dyn ConcreteParserState,
+    cc: &[CallChainElement],
+) -> bool {
     if let Some(CallChainElement::Block(Block::BraceBlock(_))) = cc.last() {
         return false;
     };
@@ -2696,8 +3195,17 @@ fn can_elide_parens_for_reserved_names(cc: &[CallChainElement]) -> bool {
             Some(CallChainElement::IdentOrOpOrKeywordOrConst(
                 IdentOrOpOrKeywordOrConst::Ident(Ident(_, ident, _)),
             )) => {
+                let can_omit_configured_parens = match cc.get(1) {
+                    Some(CallChainElement::ArgsAddStarOrExpressionListOrArgsForward(args, _)) => {
+                        ps.format_options().omit_parens_methods.contains(ident)
+                            && args_can_omit_parens(args)
+                    }
+                    _ => false,
+                };
                 let ident = ident.as_str();
-                RSPEC_METHODS.contains(ident) || GEMFILE_METHODS.contains(ident)
+                RSPEC_METHODS.contains(ident)
+                    || GEMFILE_METHODS.contains(ident)
+                    || can_omit_configured_parens
             }
             _ => false,
         };
@@ -2751,14 +3259,28 @@ fn format_call_chain_elements(
     // require this (e.g. `MethodAddArg` doesn't enforce invariants like those).
     last_call_use_parens: Option<bool>,
 ) {
-    let elide_parens = can_elide_parens_for_reserved_names(&cc);
+    let elide_parens = can_elide_parens_for_reserved_names(ps, &cc);
     // When set, force all `CallChainElement::ArgsAddStarOrExpressionListOrArgsForward`
     // to use parens, even when empty. This handles cases like `super()` where parens matter
     let mut next_args_list_must_use_parens = false;
+    // `params` in a multiline Sorbet sig gets one argument per line
+    let mut next_args_list_is_sig_params = false;
     let last_call_index = cc
         .iter()
         .rposition(|cce| matches!(cce, CallChainElement::IdentOrOpOrKeywordOrConst(..)));
     let mut has_indented = false;
+    // A comment can't come after a trailing dot, so chains that have them
+    // keep their dots leading
+    let chain_lines = cc
+        .iter()
+        .filter(|cc_elem| !matches!(cc_elem, CallChainElement::Block(..)))
+        .filter_map(|cc_elem| cc_elem.start_line());
+    let has_comments = match (chain_lines.clone().min(), chain_lines.max()) {
+        (Some(first_line), Some(last_line)) => ps.has_comments_in_line(first_line, last_line),
+        _ => false,
+    };
+    let trailing_dots =
+        ps.format_options().call_chains.dot_position == DotPosition::Trailing && !has_comments;
 
     for (index, cc_elem) in cc.into_iter().enumerate() {
         let is_last_call_args = if let Some(last_call_index) = last_call_index {
@@ -2772,6 +3294,8 @@ fn format_call_chain_elements(
             CallChainElement::IdentOrOpOrKeywordOrConst(i) => {
                 let ident = i.into_ident();
                 next_args_list_must_use_parens = ident.1 == "super" || ident.1 == ".()";
+                next_args_list_is_sig_params = ident.1 == "params"
+                    && ps.current_formatting_context() == FormattingContext::SorbetSig;
 
                 if ident.1 == ".()" {
                     ps.emit_ident(".".to_string());
@@ -2825,12 +3349,28 @@ fn format_call_chain_elements(
                             ps.shift_comments();
                         }
                     } else {
-                        ps.breakable_of(
-                            delims,
-                            Box::new(|ps| {
+                        // Args in a sig are formatted outside of its context, so only
+                        // the sig's own `params` gets one argument per line, and nested
+                        // ones like a `T.proc.params` in `returns` are laid out as usual
+                        let in_sorbet_sig =
+                            ps.current_formatting_context() == FormattingContext::SorbetSig;
+                        let format_args: RenderFunc = Box::new(move |ps| {
+                            if in_sorbet_sig {
+                                ps.with_formatting_context(
+                                    FormattingContext::ArgsList,
+                                    Box::new(|ps| {
+                                        format_list_like_thing(ps, aas, end_line, false);
+                                    }),
+                                );
+                            } else {
                                 format_list_like_thing(ps, aas, end_line, false);
-                            }),
-                        );
+                            }
+                        });
+                        if next_args_list_is_sig_params {
+                            ps.multiline_breakable_of(delims, format_args);
+                        } else {
+                            ps.breakable_of(delims, format_args);
+                        }
                         if let Some(end_line) = end_line {
                             // If we're rendering a single-line chain, force a reset so
                             // that comments end up at the current indentation level
@@ -2852,11 +3392,19 @@ fn format_call_chain_elements(
                     DotTypeOrOp::StringDot(val) => val == "::",
                     _ => false,
                 };
-                if !is_double_colon {
+                // Heredoc bodies start on the line after the dot either way,
+                // which only reads well when the dot is leading
+                if is_double_colon {
+                    format_dot(ps, d);
+                } else if trailing_dots && !ps.has_pending_heredocs() {
+                    format_dot(ps, d);
                     ps.emit_collapsing_newline();
                     ps.emit_soft_indent();
+                } else {
+                    ps.emit_collapsing_newline();
+                    ps.emit_soft_indent();
+                    format_dot(ps, d);
                 }
-                format_dot(ps, d);
             }
             CallChainElement::Expression(e) => {
                 format_expression(ps, *e);
@@ -2881,17 +3429,78 @@ pub fn format_block(ps: &mut dyn ConcreteParserState, b: Block) {
 }
 
 pub fn format_method_add_block(ps: &mut dyn ConcreteParserState, mab: MethodAddBlock) {
-    if ps.at_start_of_line() {
+    let is_statement = ps.at_start_of_line();
+    format_method_add_block_with(ps, mab, is_statement);
+}
+
+// A `do ... end` block goes to the outermost call of a command like
+// `expect foo.map { ... }`, so a `{ ... }` block only becomes one where
+// nothing it could go to instead surrounds it, i.e. statements and the
+// values of assignments
+fn format_method_add_block_with(
+    ps: &mut dyn ConcreteParserState,
+    mab: MethodAddBlock,
+    can_become_do_block: bool,
+) {
+    let is_statement = ps.at_start_of_line();
+    if is_statement {
         ps.emit_indent();
     }
 
+    // Whether the author wrote parens is lost once this is a call chain,
+    // so work it out up front for methods that want their parens preserved
+    let original_used_parens = matches!(*mab.1, CallLeft::MethodAddArg(..));
     let mut chain = (mab.1).into_call_chain();
-    chain.push(CallChainElement::Block(mab.2));
+    let last_call_use_parens =
+        match chain.as_slice() {
+            [CallChainElement::IdentOrOpOrKeywordOrConst(IdentOrOpOrKeywordOrConst::Ident(
+                Ident(_, ident, _),
+            )), CallChainElement::ArgsAddStarOrExpressionListOrArgsForward(..)]
+                if ps.format_options().preserve_parens_methods.contains(ident) =>
+            {
+                Some(original_used_parens)
+            }
+            _ => None,
+        };
+
+    // Sorbet sigs that are written across lines, or that don't fit on one,
+    // are written as `sig do ... end`
+    let (block, is_sorbet_sig_do_block) = match mab.2 {
+        Block::BraceBlock(bb) if is_statement && is_sorbet_sig(&chain, &bb) => {
+            let is_multiline = bb.3.start_line() != bb.3.end_line()
+                || chain_with_block_renders_as_multiline(
+                    ps,
+                    &chain,
+                    Block::BraceBlock(bb.clone()),
+                    last_call_use_parens,
+                );
+            if is_multiline {
+                (Block::DoBlock(bb.into_do_block()), true)
+            } else {
+                (Block::BraceBlock(bb), false)
+            }
+        }
+        block => (block, false),
+    };
+    let block = match ps.format_options().block_delimiters {
+        BlockDelimiterStyle::LineCountBased if !is_sorbet_sig_do_block => {
+            block_for_line_count(ps, &chain, block, last_call_use_parens, can_become_do_block)
+        }
+        _ => block,
+    };
+    chain.push(CallChainElement::Block(block));
 
     ps.with_start_of_line(
         false,
         Box::new(|ps| {
-            format_call_chain(ps, chain, None);
+            if is_sorbet_sig_do_block {
+                ps.with_formatting_context(
+                    FormattingContext::SorbetSig,
+                    Box::new(|ps| format_call_chain(ps, chain, last_call_use_parens)),
+                );
+            } else {
+                format_call_chain(ps, chain, last_call_use_parens);
+            }
         }),
     );
 
@@ -2900,6 +3509,103 @@ pub fn format_method_add_block(ps: &mut dyn ConcreteParserState, mab: MethodAddB
     }
 }
 
+fn chain_with_block_renders_as_multiline(
+    ps: &mut dyn ConcreteParserState,
+    chain: &[CallChainElement],
+    block: Block,
+    last_call_use_parens: Option<bool>,
+) -> bool {
+    let mut chain = chain.to_vec();
+    chain.push(CallChainElement::Block(block));
+    ps.will_render_as_multiline(Box::new(|ps| {
+        ps.emit_indent();
+        ps.with_start_of_line(
+            false,
+            Box::new(|ps| format_call_chain(ps, chain, last_call_use_parens)),
+        );
+    }))
+}
+
+// `{ ... }` for blocks that end up on one line, `do ... end` for ones that
+// don't. Only `do ... end` blocks that were written on one line are turned
+// into braces, the rest were split up on purpose.
+fn block_for_line_count(
+    ps: &mut dyn ConcreteParserState,
+    chain: &[CallChainElement],
+    block: Block,
+    last_call_use_parens: Option<bool>,
+    can_become_do_block: bool,
+) -> Block {
+    match block {
+        Block::BraceBlock(bb) if can_become_do_block => {
+            if bb.3.start_line() != bb.3.end_line()
+                || chain_with_block_renders_as_multiline(
+                    ps,
+                    chain,
+                    Block::BraceBlock(bb.clone()),
+                    last_call_use_parens,
+                )
+            {
+                Block::DoBlock(bb.into_do_block())
+            } else {
+                Block::BraceBlock(bb)
+            }
+        }
+        Block::DoBlock(db) if can_become_brace_block(ps, chain, &db, last_call_use_parens) => {
+            let bb = db.clone().into_brace_block();
+            if chain_with_block_renders_as_multiline(
+                ps,
+                chain,
+                Block::BraceBlock(bb.clone()),
+                last_call_use_parens,
+            ) {
+                Block::DoBlock(db)
+            } else {
+                Block::BraceBlock(bb)
+            }
+        }
+        block => block,
+    }
+}
+
+fn can_become_brace_block(
+    ps: &dyn ConcreteParserState,
+    chain: &[CallChainElement],
+    do_block: &DoBlock,
+    last_call_use_parens: Option<bool>,
+) -> bool {
+    let BodyStmt(_, body, rescue, else_, ensure) = do_block.2.as_ref();
+    // Braces bind to the last call, so its arguments need parens around them
+    let has_paren_less_args = last_call_use_parens == Some(false)
+        && matches!(
+            chain.last(),
+            Some(CallChainElement::ArgsAddStarOrExpressionListOrArgsForward(args, _))
+                if !args.is_empty()
+        );
+    do_block.3.start_line() == do_block.3.end_line()
+        && body.len() == 1
+        && rescue.is_none()
+        && else_.is_none()
+        && ensure.is_none()
+        && !has_paren_less_args
+        && !ps.has_comments_in_line(do_block.3.start_line(), do_block.3.end_line())
+}
+
+// `sig { ... }` or e.g. `sig(:final) { ... }`
+fn is_sorbet_sig(chain: &[CallChainElement], brace_block: &BraceBlock) -> bool {
+    let is_sig_call =
+        match chain {
+            [CallChainElement::IdentOrOpOrKeywordOrConst(IdentOrOpOrKeywordOrConst::Ident(
+                Ident(_, ident, _),
+            ))]
+            | [CallChainElement::IdentOrOpOrKeywordOrConst(IdentOrOpOrKeywordOrConst::Ident(
+                Ident(_, ident, _),
+            )), CallChainElement::ArgsAddStarOrExpressionListOrArgsForward(..)] => ident == "sig",
+            _ => false,
+        };
+    is_sig_call && brace_block.1.is_none() && !is_empty_bodystmt(&brace_block.2)
+}
+
 pub fn is_empty_bodystmt(bodystmt: &Vec<Expression>) -> bool {
     bodystmt.len() == 1 && matches!(bodystmt[0], Expression::VoidStmt(..))
 }
@@ -2945,12 +3651,14 @@ pub fn format_brace_block(ps: &mut dyn ConcreteParserState, brace_block: BraceBl
 
     ps.inline_breakable_of(
         BreakableDelims::for_brace_block(),
-        Box::new(|ps| {
-            if let Some(bv) = bv {
+        Box::new(|ps| match bv {
+            Some(bv) => {
                 format_blockvar(ps, bv);
+                render_block_contents(ps, brace_block_render_method, body, end_line);
             }
-
-            render_block_contents(ps, brace_block_render_method, body, end_line);
+            None => ps.with_implicit_block_params(Box::new(|ps| {
+                render_block_contents(ps, brace_block_render_method, body, end_line);
+            })),
         }),
     );
 }
@@ -3046,19 +3754,27 @@ pub fn format_do_block(ps: &mut dyn ConcreteParserState, do_block: DoBlock) {
     let body = do_block.2;
     let end_line = do_block.3.end_line();
 
+    let has_block_var = bv.is_some();
     if let Some(bv) = bv {
         format_blockvar(ps, bv)
     }
 
-    ps.new_block(Box::new(|ps| {
-        ps.with_start_of_line(
-            true,
-            Box::new(|ps| {
-                ps.emit_newline();
-                format_bodystmt(ps, body, end_line);
-            }),
-        );
-    }));
+    let render_body: RenderFunc = Box::new(|ps| {
+        ps.new_block(Box::new(|ps| {
+            ps.with_start_of_line(
+                true,
+                Box::new(|ps| {
+                    ps.emit_newline();
+                    format_bodystmt(ps, body, end_line);
+                }),
+            );
+        }));
+    });
+    if has_block_var {
+        render_body(ps);
+    } else {
+        ps.with_implicit_block_params(render_body);
+    }
 
     ps.with_start_of_line(
         true,
@@ -3403,14 +4119,15 @@ pub fn format_stabby_lambda(ps: &mut dyn ConcreteParserState, sl: StabbyLambda)
         false,
         Box::new(|ps| {
             ps.emit_keyword("->".to_string());
-            if params.is_present() {
+            let has_params = params.is_present();
+            if has_params {
                 ps.emit_space();
             }
             format_paren_or_params(ps, params);
 
             // Curly blocks are always represented as ExpressionLists (stmt_add nodes)
             // while do/end blocks are BodyStmt nodes
-            match body {
+            let render_body: RenderFunc = Box::new(|ps| match body {
                 ExpressionListOrBodyStmt::ExpressionList(body) => {
                     let brace_block_render_method =
                         get_brace_block_render_method(ps, start_line, end_line, &body);
@@ -3442,6 +4159,11 @@ pub fn format_stabby_lambda(ps: &mut dyn ConcreteParserState, sl: StabbyLambda)
                         }),
                     );
                 }
+            });
+            if has_params {
+                render_body(ps);
+            } else {
+                ps.with_implicit_block_params(render_body);
             }
         }),
     );
@@ -3458,7 +4180,7 @@ pub fn format_imaginary(ps: &mut dyn ConcreteParserState, imaginary: Imaginary)
         ps.emit_indent();
     }
 
-    handle_string_and_linecol(ps, imaginary.1, imaginary.2);
+    format_numeric_literal(ps, imaginary.1, imaginary.2);
 
     if ps.at_start_of_line() {
         ps.emit_newline();
@@ -3470,7 +4192,7 @@ pub fn format_rational(ps: &mut dyn ConcreteParserState, rational: Rational) {
         ps.emit_indent();
     }
 
-    handle_string_and_linecol(ps, rational.1, rational.2);
+    format_numeric_literal(ps, rational.1, rational.2);
 
     if ps.at_start_of_line() {
         ps.emit_newline();
@@ -3536,25 +4258,59 @@ pub fn format_for(ps: &mut dyn ConcreteParserState, forloop: For) {
 }
 
 pub fn format_ifop(ps: &mut dyn ConcreteParserState, ifop: IfOp) {
+    // A ternary that's a statement of its own means the same thing as an `if`
+    let ifop = if ps.at_start_of_line() {
+        match ternary_as_conditional(ps, Expression::IfOp(ifop)) {
+            Expression::IfOp(ifop) => ifop,
+            Expression::If(ifs) => return format_if(ps, ifs),
+            _ => unreachable!("only ever returns an if or the ternary it was given"),
+        }
+    } else {
+        ifop
+    };
+
     if ps.at_start_of_line() {
         ps.emit_indent();
     }
 
+    // Breaking the ternary puts a `\\` at the end of the lines, which can't
+    // come after a comment
+    let (condition, then_branch, else_branch) = (ifop.1, ifop.2, ifop.3);
+    let first_line = condition.start_line();
+    let last_line = else_branch.start_line();
+    let has_comments = match (first_line, last_line) {
+        (Some(first_line), Some(last_line)) => ps.has_comments_in_line(first_line, last_line),
+        _ => true,
+    };
+
     ps.with_start_of_line(
         false,
         Box::new(|ps| {
             ps.with_formatting_context(
                 FormattingContext::IfOp,
                 Box::new(|ps| {
-                    format_expression(ps, *ifop.1);
-                    ps.emit_space();
-                    ps.emit_keyword("?".to_string());
-                    ps.emit_space();
-                    format_expression(ps, *ifop.2);
-                    ps.emit_space();
-                    ps.emit_keyword(":".to_string());
-                    ps.emit_space();
-                    format_expression(ps, *ifop.3);
+                    if has_comments {
+                        format_expression(ps, *condition);
+                        ps.emit_space();
+                        ps.emit_keyword("?".to_string());
+                        ps.emit_space();
+                        format_expression(ps, *then_branch);
+                        ps.emit_space();
+                        ps.emit_keyword(":".to_string());
+                        ps.emit_space();
+                        format_expression(ps, *else_branch);
+                    } else {
+                        ps.inline_breakable_of(
+                            BreakableDelims::for_binary_op(),
+                            Box::new(|ps| {
+                                ps.dedent(Box::new(|ps| {
+                                    format_expression(ps, *condition);
+                                }));
+                                format_ifop_branch(ps, "?", *then_branch);
+                                format_ifop_branch(ps, ":", *else_branch);
+                            }),
+                        );
+                    }
                 }),
             );
         }),
@@ -3565,6 +4321,69 @@ pub fn format_ifop(ps: &mut dyn ConcreteParserState, ifop: IfOp) {
     }
 }
 
+// Either ` ? branch`, or a line of its own when the ternary is too long
+fn format_ifop_branch(ps: &mut dyn ConcreteParserState, keyword: &str, branch: Expression) {
+    ps.emit_line_continuation();
+    ps.emit_soft_newline();
+    ps.emit_soft_indent();
+    ps.emit_keyword(keyword.to_string());
+    ps.emit_space();
+    ps.reset_space_count();
+    format_expression(ps, branch);
+}
+
+/// Rewrites a chain of ternaries like `a ? 1 : b ? 2 : 3` into an
+/// `if`/`elsif`/`else` when it's longer than `max_ternary_depth` allows.
+/// Callers only pass in expressions whose value is used the same way
+/// either way, e.g. the right hand side of an assignment.
+fn ternary_as_conditional(ps: &dyn ConcreteParserState, expr: Expression) -> Expression {
+    let max_depth = match ps.format_options().max_ternary_depth {
+        Some(max_depth) => max_depth,
+        None => return expr,
+    };
+    let ifop = match expr {
+        Expression::IfOp(ifop) => ifop,
+        expr => return expr,
+    };
+
+    let mut depth = 1;
+    let mut last_branch = &*ifop.3;
+    while let Expression::IfOp(nested) = last_branch {
+        depth += 1;
+        last_branch = &*nested.3;
+    }
+    if depth <= max_depth {
+        return Expression::IfOp(ifop);
+    }
+
+    let start_line = ifop
+        .1
+        .start_line()
+        .unwrap_or_else(|| ps.current_line_number());
+    let end_line = last_branch.start_line().unwrap_or(start_line);
+    let start_end = StartEnd(start_line, end_line);
+    Expression::If(If(
+        if_tag,
+        ifop.1,
+        vec![*ifop.2],
+        Some(ternary_tail(*ifop.3, &start_end)),
+        start_end,
+    ))
+}
+
+fn ternary_tail(expr: Expression, start_end: &StartEnd) -> ElsifOrElse {
+    match expr {
+        Expression::IfOp(ifop) => ElsifOrElse::Elsif(Elsif(
+            elsif_tag,
+            ifop.1,
+            vec![*ifop.2],
+            Some(Box::new(ternary_tail(*ifop.3, start_end))),
+            start_end.clone(),
+        )),
+        expr => ElsifOrElse::Else(Else(else_tag, vec![expr], start_end.clone())),
+    }
+}
+
 pub fn format_return0(ps: &mut dyn ConcreteParserState, r: Return0) {
     format_keyword(
         ps,
@@ -3586,7 +4405,8 @@ pub fn format_opassign(ps: &mut dyn ConcreteParserState, opassign: OpAssign) {
             ps.emit_space();
             format_op(ps, opassign.2);
             ps.emit_space();
-            format_expression(ps, *opassign.3);
+            let value = ternary_as_conditional(ps, *opassign.3);
+            format_expression(ps, value);
         }),
     );
 
@@ -3594,9 +4414,11 @@ pub fn format_opassign(ps: &mut dyn ConcreteParserState, opassign: OpAssign) {
         ps.emit_newline();
     }
 }
-pub fn format_to_proc(ps: &mut dyn ConcreteParserState, e: Box<Expression>) {
+pub fn format_to_proc(ps: &mut dyn ConcreteParserState, e: Option<Box<Expression>>) {
     ps.emit_ident("&".to_string());
-    ps.with_start_of_line(false, Box::new(|ps| format_expression(ps, *e)));
+    if let Some(e) = e {
+        ps.with_start_of_line(false, Box::new(|ps| format_expression(ps, *e)));
+    }
 }
 
 pub fn format_zsuper(ps: &mut dyn ConcreteParserState, start_end: StartEnd) {
@@ -3715,7 +4537,10 @@ pub fn format_expression(ps: &mut dyn ConcreteParserState, expression: Expressio
         Expression::SymbolLiteral(sl) => format_symbol_literal(ps, sl),
         Expression::Alias(alias) => format_alias(ps, alias),
         Expression::Array(array) => format_array(ps, array),
-        Expression::StringLiteral(sl) => format_string_literal(ps, sl),
+        Expression::StringLiteral(sl) => match split_long_string_literal(ps, &sl) {
+            Some(sc) => format_string_concat(ps, sc),
+            None => format_string_literal(ps, sl),
+        },
         Expression::XStringLiteral(xsl) => format_xstring_literal(ps, xsl),
         Expression::Assign(assign) => format_assign(ps, assign),
         Expression::VarRef(vr) => format_var_ref(ps, vr),
@@ -3776,7 +4601,11 @@ pub fn format_expression(ps: &mut dyn ConcreteParserState, expression: Expressio
 }
 
 pub fn format_program(ps: &mut BaseParserState, program: Program, end_data: Option<&str>) {
-    ps.flush_start_of_file_comments();
+    let program_has_code = program
+        .1
+        .iter()
+        .any(|e| !matches!(e, Expression::VoidStmt(_)));
+    ps.flush_start_of_file_comments(program_has_code);
     debug!("{:?}", program);
     for expression in program.1 {
         format_expression(ps, expression);
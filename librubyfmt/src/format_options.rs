@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Settings for the parts of the output that vary between projects. The
+/// defaults match rubyfmt's own style, so formatting with
+/// `FormatOptions::default()` is the same as calling `format_buffer`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatOptions {
+    /// Methods that keep whatever parens the author wrote, the same way
+    /// `require` and Gemfile methods like `gem` are treated.
+    pub preserve_parens_methods: HashSet<String>,
+    /// Methods that never get parens when they're called as a statement,
+    /// e.g. `validates :name, presence: true` in a Rails model.
+    pub omit_parens_methods: HashSet<String>,
+    /// Which quotes to use for string literals and dynamic symbols. `%q` and
+    /// `%Q` strings are rewritten with quotes like any other string, heredocs
+    /// and percent arrays aren't affected.
+    pub quote_style: QuoteStyle,
+    pub hash_key_style: HashKeyStyle,
+    /// Whether lists that get broken across multiple lines end with a comma
+    pub trailing_commas: TrailingCommas,
+    /// Where `&&`, `||`, `and`, and `or` go when a chain of them gets broken
+    /// across multiple lines
+    pub boolean_operators: BooleanOperatorStyle,
+    /// How method call chains like `foo.bar.baz` get broken across
+    /// multiple lines
+    pub call_chains: CallChainOptions,
+    /// Whether blocks are written with `{ ... }` or `do ... end`
+    pub block_delimiters: BlockDelimiterStyle,
+    /// Split string literals that run past the maximum line length into
+    /// `"..." \` concatenations
+    pub split_long_strings: bool,
+    /// Rewrite chains of more than this many ternaries (`a ? 1 : b ? 2 : 3`
+    /// is two) into `if`/`elsif`/`else`, `None` leaves them as ternaries
+    pub max_ternary_depth: Option<usize>,
+    /// Rewrite numeric literals into a canonical spelling, `None` leaves
+    /// them exactly as they were written
+    pub numeric_literals: Option<NumericLiteralStyle>,
+    /// Sort blocks of consecutive `require` and `require_relative` calls
+    pub sort_requires: bool,
+    pub blank_lines: BlankLineOptions,
+    pub access_modifiers: AccessModifierOptions,
+    pub magic_comments: MagicCommentOptions,
+    /// Rewrap prose in comments that runs past the maximum line length
+    pub reflow_comments: bool,
+    /// Format the code after YARD `@example` tags and in RDoc verbatim
+    /// sections of comments
+    pub format_doc_examples: bool,
+    /// Identifiers (e.g. `RUBY`) of `<<~` heredocs whose body is Ruby code
+    /// that gets formatted too
+    pub ruby_heredocs: HashSet<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteStyle {
+    /// Always use double quotes
+    #[default]
+    Double,
+    /// Use single quotes for strings without interpolation or escapes that
+    /// need double quotes, unless they contain a single quote themselves
+    Single,
+    /// Keep single quoted strings single quoted where their contents allow it
+    Preserve,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashKeyStyle {
+    /// Use labels when every key is already a label, otherwise write every
+    /// key with a hash rocket
+    #[default]
+    Consistent,
+    /// Also turn symbol keys written with hash rockets into labels, as long
+    /// as every key in the hash can be written as one
+    PreferLabels,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingCommas {
+    #[default]
+    Never,
+    /// Array and hash literals as well as method call arguments
+    Always,
+    /// Only array and hash literals, method call arguments never get one
+    OnlyLiterals,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BooleanOperatorStyle {
+    /// At the end of each line but the last
+    #[default]
+    Trailing,
+    /// At the start of each line but the first, which needs a `\` at the
+    /// end of the line before it
+    Leading,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NumericLiteralStyle {
+    /// How many digits go between each `_` in a decimal integer
+    pub group_size: usize,
+    /// Decimal integers shorter than this don't get separators
+    pub min_digits: usize,
+}
+
+impl Default for NumericLiteralStyle {
+    fn default() -> Self {
+        NumericLiteralStyle {
+            group_size: 3,
+            min_digits: 5,
+        }
+    }
+}
+
+/// Rules for blank lines. The rules that add or remove blank lines are off
+/// by default, in which case blank lines are kept where the author wrote
+/// them (aside from the ones rubyfmt always adds, e.g. after `end`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlankLineOptions {
+    /// The most blank lines that can appear in a row
+    pub max_consecutive: usize,
+    /// Always put a blank line before a bare `private`, `protected`, or
+    /// `public`, unless it starts the body or has a comment above it
+    pub before_access_modifiers: bool,
+    /// Always put a blank line after a bare `private`, `protected`, or `public`
+    pub after_access_modifiers: bool,
+    /// Remove blank lines at the start and end of class, module, and def bodies
+    pub trim_body_edges: bool,
+    /// Always put a blank line between two method definitions, including
+    /// endless and single line ones
+    pub between_methods: bool,
+}
+
+impl Default for BlankLineOptions {
+    fn default() -> Self {
+        BlankLineOptions {
+            max_consecutive: 1,
+            before_access_modifiers: false,
+            after_access_modifiers: false,
+            trim_body_edges: false,
+            between_methods: false,
+        }
+    }
+}
+
+/// How bare `private`, `protected`, and `public` calls in class and module
+/// bodies are laid out. The blank lines around them are up to `BlankLineOptions`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessModifierOptions {
+    /// Indent everything after a bare modifier one level deeper than the modifier
+    pub indent_sections: bool,
+    pub style: AccessModifierStyle,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockDelimiterStyle {
+    /// Keep whichever delimiters the author wrote
+    #[default]
+    Preserve,
+    /// `{ ... }` for blocks that fit on one line and `do ... end` for ones
+    /// that don't, wherever swapping them can't change which call the block
+    /// goes to
+    LineCountBased,
+}
+
+/// How call chains like `foo.bar.baz` are laid out when they get broken
+/// across multiple lines
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CallChainOptions {
+    /// Which line the `.` of each call goes on
+    pub dot_position: DotPosition,
+    /// Put as many calls on a line as fit, instead of one call per line
+    pub fill_lines: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DotPosition {
+    /// At the start of the line the call is on, e.g. `.bar`
+    #[default]
+    Leading,
+    /// At the end of the line before the call, e.g. `foo.`
+    Trailing,
+}
+
+/// Rules for the magic comments (e.g. `# frozen_string_literal: true`) in the
+/// comments that start a file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MagicCommentOptions {
+    /// Spell magic comments one way, put them in order (shebang, encoding,
+    /// `frozen_string_literal`, then the rest), and follow them with a blank line
+    pub normalize: bool,
+    /// Add `# frozen_string_literal: true` to files that don't say either way
+    pub add_frozen_string_literal: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessModifierStyle {
+    /// Keep modifiers the way the author wrote them
+    #[default]
+    Preserve,
+    /// Turn a bare `private` followed by methods into `private def` on each
+    /// method. Only sections made up entirely of instance methods change,
+    /// since the bare call also affects things like `attr_reader`.
+    Inline,
+    /// Turn `private def` calls into a bare `private` followed by the methods.
+    /// Only calls at the end of the body change, since a bare call also
+    /// affects everything after it.
+    Sections,
+}
@@ -1,5 +1,6 @@
 use crate::line_metadata::LineMetadata;
 use crate::line_tokens::*;
+use crate::require_sorting::{classify_line, RequireBlock};
 #[cfg(debug_assertions)]
 use log::debug;
 use std::convert::TryInto;
@@ -17,16 +18,25 @@ pub struct Intermediary {
     index_of_last_hard_newline: usize,
     current_line_metadata: LineMetadata,
     previous_line_metadata: Option<LineMetadata>,
+    max_blank_lines: usize,
+    // Only collected when `sort_requires` is on
+    require_block: Option<RequireBlock>,
     pub additional_indent: u32,
 }
 
 impl Intermediary {
-    pub fn new() -> Self {
+    pub fn new(max_blank_lines: usize, sort_requires: bool) -> Self {
         Intermediary {
             tokens: vec![],
             current_line_metadata: LineMetadata::new(),
             previous_line_metadata: None,
             index_of_last_hard_newline: 0,
+            max_blank_lines,
+            require_block: if sort_requires {
+                Some(RequireBlock::default())
+            } else {
+                None
+            },
             additional_indent: 0,
         }
     }
@@ -83,7 +93,8 @@ impl Intermediary {
         )
     }
 
-    pub fn into_tokens(self) -> Vec<ConcreteLineToken> {
+    pub fn into_tokens(mut self) -> Vec<ConcreteLineToken> {
+        self.finish_require_block();
         self.tokens
     }
 
@@ -104,10 +115,19 @@ impl Intermediary {
 
         match &lt {
             ConcreteLineToken::HardNewLine => {
-                if let Some(prev) = &self.previous_line_metadata {
-                    if !self.current_line_metadata.has_require() && prev.has_require() {
-                        self.insert_trailing_blankline(BlanklineReason::EndOfRequireBlock);
+                // A `require` that couldn't be sorted (e.g. `require "a" if b`)
+                // still doesn't get a blank line between it and the others
+                let end_of_require_block = match self.collect_require_line() {
+                    Some(end_of_require_block) => {
+                        end_of_require_block && !self.current_line_metadata.has_require()
                     }
+                    None => matches!(
+                        &self.previous_line_metadata,
+                        Some(prev) if prev.has_require() && !self.current_line_metadata.has_require()
+                    ),
+                };
+                if end_of_require_block {
+                    self.insert_trailing_blankline(BlanklineReason::EndOfRequireBlock);
                 }
 
                 let mut md = LineMetadata::new();
@@ -115,17 +135,15 @@ impl Intermediary {
                 self.previous_line_metadata = Some(md);
                 self.index_of_last_hard_newline = self.tokens.len();
 
-                if self.tokens.len() >= 2 {
-                    if let (
-                        Some(&ConcreteLineToken::HardNewLine),
-                        Some(&ConcreteLineToken::HardNewLine),
-                    ) = (
-                        self.tokens.get(self.index_of_last_hard_newline - 2),
-                        self.tokens.get(self.index_of_last_hard_newline - 1),
-                    ) {
-                        do_push = false;
-                        self.index_of_last_hard_newline = self.tokens.len() - 1;
-                    }
+                // Drop newlines past the most blank lines we allow in a row
+                let max_newlines = self.max_blank_lines + 1;
+                if self.tokens.len() >= max_newlines
+                    && self.tokens[self.tokens.len() - max_newlines..]
+                        .iter()
+                        .all(|t| t == &ConcreteLineToken::HardNewLine)
+                {
+                    do_push = false;
+                    self.index_of_last_hard_newline = self.tokens.len() - 1;
                 }
             }
             ConcreteLineToken::ModuleKeyword | ConcreteLineToken::ClassKeyword => {
@@ -150,8 +168,12 @@ impl Intermediary {
                 }
             }
             ConcreteLineToken::DirectPart { part } => {
-                if part == "require" && self.tokens.last().map(|t| t.is_indent()).unwrap_or(false) {
-                    self.current_line_metadata.set_has_require();
+                if self.tokens.last().map(|t| t.is_indent()).unwrap_or(false) {
+                    if part == "require" {
+                        self.current_line_metadata.set_has_require();
+                    } else if part == "require_relative" {
+                        self.current_line_metadata.set_has_require_relative();
+                    }
                 }
             }
             ConcreteLineToken::Comment { .. } => {
@@ -196,6 +218,71 @@ impl Intermediary {
         self.debug_assert_newlines();
     }
 
+    // Adds the line that's just been finished to the `require` block being
+    // sorted. Returns whether the line needs the blank line that ends a
+    // block of `require`s above it, or `None` when the block has nothing to
+    // say about it. Lines inside of a block never get one, since `require`
+    // and `require_relative` calls get sorted in with each other.
+    fn collect_require_line(&mut self) -> Option<bool> {
+        let block = self.require_block.as_ref()?;
+        let kind = classify_line(
+            &self.tokens[self.current_line_start()..],
+            &self.current_line_metadata,
+        );
+        let end_of_require_block = if block.accepts(&kind) {
+            None
+        } else {
+            self.finish_require_block()
+        };
+
+        let line = self.current_line_start()..self.tokens.len();
+        let block = self
+            .require_block
+            .as_mut()
+            .expect("checked that sorting is on above");
+        let in_block = block.has_calls();
+        block.push(kind, line);
+        if in_block {
+            Some(false)
+        } else {
+            end_of_require_block
+        }
+    }
+
+    // Sorts the block that's being collected, returning whether the line
+    // after it needs a blank line, or `None` if there was no block
+    fn finish_require_block(&mut self) -> Option<bool> {
+        let block = match &mut self.require_block {
+            Some(block) => mem::take(block),
+            None => return None,
+        };
+        if !block.has_calls() {
+            return None;
+        }
+
+        block.sort(&mut self.tokens);
+        match block.trailing_comments_start() {
+            // The comments aren't above a call, so the blank line goes
+            // before them instead
+            Some(comments_start) => {
+                if block.has_require() {
+                    self.tokens
+                        .insert(comments_start, ConcreteLineToken::HardNewLine);
+                    self.index_of_last_hard_newline += 1;
+                }
+                Some(false)
+            }
+            None => Some(block.has_require()),
+        }
+    }
+
+    fn current_line_start(&self) -> usize {
+        match self.tokens.get(self.index_of_last_hard_newline) {
+            Some(ConcreteLineToken::HardNewLine) => self.index_of_last_hard_newline + 1,
+            _ => 0,
+        }
+    }
+
     fn handle_end(&mut self) {
         self.current_line_metadata.set_has_end();
     }
@@ -17,23 +17,36 @@ pub type RawStatus = i64;
 
 #[macro_use]
 pub mod ruby;
+mod access_modifiers;
+mod blank_lines;
 mod comment_block;
+mod comment_reflow;
 mod de;
 mod delimiters;
+mod doc_examples;
 mod file_comments;
 mod format;
+mod format_options;
 mod heredoc_string;
 mod intermediary;
 mod line_metadata;
 mod line_tokens;
+mod magic_comments;
+mod numeric_literals;
 mod parser_state;
 mod render_queue_writer;
 mod render_targets;
+mod require_sorting;
 mod ripper_tree_types;
 mod ruby_ops;
 mod types;
 
 use file_comments::FileComments;
+pub use format_options::{
+    AccessModifierOptions, AccessModifierStyle, BlankLineOptions, BlockDelimiterStyle,
+    BooleanOperatorStyle, CallChainOptions, DotPosition, FormatOptions, HashKeyStyle,
+    MagicCommentOptions, NumericLiteralStyle, QuoteStyle, TrailingCommas,
+};
 use parser_state::BaseParserState;
 use ruby_ops::{load_rubyfmt, ParseError, Parser, RipperTree};
 
@@ -44,7 +57,6 @@ use simplelog::{ConfigBuilder, LevelFilter, TermLogger, TerminalMode};
 
 extern "C" {
     pub fn Init_ripper();
-    pub fn rb_gc_disable();
 }
 
 pub struct RubyfmtString(Box<str>);
@@ -90,10 +102,23 @@ pub enum FormatError {
 }
 
 pub fn format_buffer(buf: &str) -> Result<String, RichFormatError> {
+    format_buffer_with_options(buf, &FormatOptions::default())
+}
+
+pub fn format_buffer_with_options(
+    buf: &str,
+    options: &FormatOptions,
+) -> Result<String, RichFormatError> {
     let (tree, file_comments, end_data) = run_parser_on(buf)?;
     let out_data = vec![];
     let mut output = Cursor::new(out_data);
-    toplevel_format_program(&mut output, tree, file_comments, end_data)?;
+    format_program_with_options(
+        &mut output,
+        tree,
+        file_comments,
+        end_data.as_deref(),
+        options,
+    )?;
     output.flush().expect("flushing to a vec should never fail");
     Ok(String::from_utf8(output.into_inner()).expect("we never write invalid UTF-8"))
 }
@@ -194,7 +219,6 @@ unsafe fn load_ripper() -> Result<(), ()> {
         "../ruby_checkout/ext/ripper/lib/ripper/sexp.rb"
     ))?;
 
-    rb_gc_disable();
     Ok(())
 }
 
@@ -204,7 +228,23 @@ pub fn toplevel_format_program<W: Write>(
     file_comments: FileComments,
     end_data: Option<&str>,
 ) -> Result<(), RichFormatError> {
-    let mut ps = BaseParserState::new(file_comments);
+    format_program_with_options(
+        writer,
+        tree,
+        file_comments,
+        end_data,
+        &FormatOptions::default(),
+    )
+}
+
+fn format_program_with_options<W: Write>(
+    writer: &mut W,
+    tree: RipperTree,
+    file_comments: FileComments,
+    end_data: Option<&str>,
+    options: &FormatOptions,
+) -> Result<(), RichFormatError> {
+    let mut ps = BaseParserState::new(file_comments, options.clone());
     let v: ripper_tree_types::Program =
         de::from_value(tree).map_err(RichFormatError::RipperParseFailure)?;
 
@@ -215,7 +255,7 @@ pub fn toplevel_format_program<W: Write>(
     Ok(())
 }
 
-fn run_parser_on(buf: &str) -> Result<(RipperTree, FileComments, Option<&str>), RichFormatError> {
+fn run_parser_on(buf: &str) -> Result<(RipperTree, FileComments, Option<String>), RichFormatError> {
     Parser::new(buf).parse().map_err(|e| match e {
         ParseError::SyntaxError => RichFormatError::SyntaxError,
         ParseError::OtherRubyError(s) => RichFormatError::OtherRubyError(s),
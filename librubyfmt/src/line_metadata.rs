@@ -7,6 +7,7 @@ pub struct LineMetadata {
     do_keyword: bool,
     indent_level: Option<u32>,
     require: bool,
+    require_relative: bool,
 }
 
 impl LineMetadata {
@@ -23,6 +24,7 @@ impl LineMetadata {
             do_keyword: false,
             indent_level: None,
             require: false,
+            require_relative: false,
         }
     }
 
@@ -34,6 +36,14 @@ impl LineMetadata {
         self.require
     }
 
+    pub fn set_has_require_relative(&mut self) {
+        self.require_relative = true;
+    }
+
+    pub fn has_require_relative(&self) -> bool {
+        self.require_relative
+    }
+
     pub fn observe_indent_level(&mut self, level: u32) {
         self.indent_level = Some(level);
     }
@@ -20,6 +20,37 @@ pub fn clats_indent(depth: ColNumber) -> ConcreteLineTokenAndTargets {
     ConcreteLineTokenAndTargets::ConcreteLineToken(ConcreteLineToken::Indent { depth })
 }
 
+/// The tokens between two `HardNewLine`s, for passes that work on whole
+/// lines of the final output
+pub type Line = Vec<ConcreteLineToken>;
+
+pub fn split_lines(tokens: Vec<ConcreteLineToken>) -> Vec<Line> {
+    let mut lines = vec![Vec::new()];
+    for token in tokens {
+        if token == ConcreteLineToken::HardNewLine {
+            lines.push(Vec::new());
+        } else {
+            lines
+                .last_mut()
+                .expect("lines always has a last element")
+                .push(token);
+        }
+    }
+    lines
+}
+
+pub fn join_lines(lines: Vec<Line>) -> Vec<ConcreteLineToken> {
+    let mut tokens = Vec::new();
+    let line_count = lines.len();
+    for (idx, line) in lines.into_iter().enumerate() {
+        tokens.extend(line);
+        if idx != line_count - 1 {
+            tokens.push(ConcreteLineToken::HardNewLine);
+        }
+    }
+    tokens
+}
+
 // represents something that will actually end up as a ruby token, as opposed to
 // something that has to be transformed to become a ruby token
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -34,6 +65,8 @@ pub enum ConcreteLineToken {
     ModKeyword { contents: String },
     ConditionalKeyword { contents: String },
     DirectPart { part: String },
+    // A bare `private`, `protected`, or `public` in a class or module body
+    AccessModifier { name: String },
     CommaSpace,
     Comma,
     Space,
@@ -49,6 +82,7 @@ pub enum ConcreteLineToken {
     CloseParen,
     Op { op: String },
     DoubleQuote,
+    SingleQuote,
     LTStringContent { content: String },
     SingleSlash,
     Comment { contents: String },
@@ -77,6 +111,7 @@ impl ConcreteLineToken {
             Self::DefKeyword => "def".to_string(),
             Self::ModuleKeyword => "module".to_string(),
             Self::DirectPart { part } => part,
+            Self::AccessModifier { name } => name,
             Self::CommaSpace => ", ".to_string(),
             Self::Comma => ",".to_string(),
             Self::Space => " ".to_string(),
@@ -92,6 +127,7 @@ impl ConcreteLineToken {
             Self::CloseParen => ")".to_string(),
             Self::Op { op } => op,
             Self::DoubleQuote => "\"".to_string(),
+            Self::SingleQuote => "'".to_string(),
             Self::LTStringContent { content } => content,
             Self::SingleSlash => "\\".to_string(),
             Self::Comment { contents } => contents,
@@ -135,6 +171,7 @@ impl ConcreteLineToken {
             Keyword { keyword: contents }
             | Op { op: contents }
             | DirectPart { part: contents }
+            | AccessModifier { name: contents }
             | LTStringContent { content: contents }
             | Comment { contents }
             | Delim { contents }
@@ -143,7 +180,7 @@ impl ConcreteLineToken {
             | ModKeyword { contents } => contents.len(),
             HardNewLine | Comma | Space | Dot | OpenSquareBracket | CloseSquareBracket
             | OpenCurlyBracket | CloseCurlyBracket | OpenParen | CloseParen | SingleSlash
-            | DoubleQuote => 1,
+            | DoubleQuote | SingleQuote => 1,
             DoKeyword | CommaSpace | LonelyOperator | ColonColon => 2,
             DefKeyword | Ellipsis | End => 3, // "def"/"..."/"end"
             ClassKeyword => 5,                // "class"
@@ -276,6 +313,10 @@ pub enum AbstractLineToken {
     CollapsingNewLine(Option<Vec<HeredocString>>),
     SoftNewline(Option<Vec<HeredocString>>),
     SoftIndent { depth: u32 },
+    // Only ever rendered by a multiline breakable that takes trailing commas
+    TrailingComma,
+    // A ` \` at the end of a line, only ever rendered by a multiline breakable
+    LineContinuation,
     BreakableEntry(BreakableEntry),
     BreakableCallChainEntry(BreakableCallChainEntry),
 }
@@ -298,7 +339,7 @@ impl AbstractLineToken {
                 res.extend(Self::shimmy_and_shake_heredocs(heredoc_strings));
                 res
             }
-            Self::SoftIndent { .. } => Vec::new(),
+            Self::SoftIndent { .. } | Self::TrailingComma | Self::LineContinuation => Vec::new(),
             Self::ConcreteLineToken(clt) => {
                 vec![ConcreteLineTokenAndTargets::ConcreteLineToken(clt)]
             }
@@ -326,6 +367,8 @@ impl AbstractLineToken {
                     ConcreteLineToken::Indent { depth },
                 )]
             }
+            Self::TrailingComma => Vec::new(),
+            Self::LineContinuation => vec![clats_direct_part(" \\".to_string())],
             Self::ConcreteLineToken(clt) => {
                 vec![ConcreteLineTokenAndTargets::ConcreteLineToken(clt)]
             }
@@ -0,0 +1,193 @@
+use crate::format_options::MagicCommentOptions;
+
+const FROZEN_STRING_LITERAL: &str = "# frozen_string_literal: true";
+
+// The order magic comments go in, anything else keeps its relative order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MagicKind {
+    Encoding,
+    FrozenStringLiteral,
+    Other,
+}
+
+/// Rewrites the comments that start a file. Only the magic comments at the
+/// very top (after a shebang, which always stays first) get reordered, since
+/// Ruby only reads an encoding comment from the first two lines. An empty
+/// string at the end means a blank line should come after the comments.
+pub fn normalize_magic_comments(
+    comments: Vec<String>,
+    options: MagicCommentOptions,
+) -> Vec<String> {
+    let mut comments = comments.into_iter().peekable();
+    let shebang = comments.next_if(|c| c.starts_with("#!"));
+
+    let mut magic = Vec::new();
+    while let Some(comment) = comments.peek() {
+        match classify_magic_comment(comment, magic.is_empty()) {
+            Some((kind, canonical)) => {
+                let comment = comments.next().expect("we peeked it");
+                magic.push((
+                    kind,
+                    if options.normalize {
+                        canonical
+                    } else {
+                        comment
+                    },
+                ));
+            }
+            None => break,
+        }
+    }
+
+    // Further down an encoding comment doesn't do anything, so it's more
+    // likely to be prose than a typo
+    let rest = comments
+        .map(|comment| match parse_magic_comment(&comment) {
+            Some((key, value)) if options.normalize && key != "encoding" => {
+                format!("# {}: {}", key, value)
+            }
+            _ => comment,
+        })
+        .collect::<Vec<_>>();
+
+    let has_frozen_string_literal = magic
+        .iter()
+        .map(|(_, comment)| comment)
+        .chain(rest.iter())
+        .any(|comment| {
+            magic_comment_settings(comment)
+                .iter()
+                .any(|(key, _)| key == "frozen_string_literal")
+        });
+    let add_frozen_string_literal = options.add_frozen_string_literal && !has_frozen_string_literal;
+    if add_frozen_string_literal {
+        let after_encoding = magic
+            .iter()
+            .take_while(|(kind, _)| *kind == MagicKind::Encoding)
+            .count();
+        magic.insert(
+            after_encoding,
+            (
+                MagicKind::FrozenStringLiteral,
+                FROZEN_STRING_LITERAL.to_string(),
+            ),
+        );
+    }
+
+    if options.normalize {
+        magic.sort_by_key(|(kind, _)| *kind);
+    }
+
+    let mut result = shebang
+        .into_iter()
+        .chain(magic.into_iter().map(|(_, comment)| comment))
+        .collect::<Vec<_>>();
+    if !result.is_empty() && (options.normalize || add_frozen_string_literal) {
+        result.push(String::new());
+    }
+    result.extend(rest);
+    result
+}
+
+fn classify_magic_comment(
+    comment: &str,
+    in_encoding_position: bool,
+) -> Option<(MagicKind, String)> {
+    match parse_magic_comment(comment) {
+        // An encoding comment anywhere else is ignored by Ruby, and moving
+        // it up would change what the file means
+        Some((key, value)) if key == "encoding" && in_encoding_position => {
+            Some((MagicKind::Encoding, format!("# {}: {}", key, value)))
+        }
+        Some((key, value)) if key == "frozen_string_literal" => Some((
+            MagicKind::FrozenStringLiteral,
+            format!("# {}: {}", key, value),
+        )),
+        Some((key, value)) => Some((MagicKind::Other, format!("# {}: {}", key, value))),
+        // Ruby finds the encoding in lines like `# vim: set fileencoding=utf-8 :`
+        // too, those are kept as they are
+        None if in_encoding_position
+            && (comment.contains("coding:") || comment.contains("coding=")) =>
+        {
+            Some((MagicKind::Encoding, comment.to_string()))
+        }
+        // Emacs style comments can set several at once, those are kept as
+        // they are and sort by the setting that has to come first
+        None => {
+            let settings = magic_comment_settings(comment);
+            let has_key = |k: &str| settings.iter().any(|(key, _)| key == k);
+            let kind = if has_key("encoding") {
+                if in_encoding_position {
+                    MagicKind::Encoding
+                } else {
+                    MagicKind::Other
+                }
+            } else if has_key("frozen_string_literal") {
+                MagicKind::FrozenStringLiteral
+            } else if settings.is_empty() {
+                return None;
+            } else {
+                MagicKind::Other
+            };
+            Some((kind, comment.to_string()))
+        }
+    }
+}
+
+// The canonical key and value of a magic comment, e.g. `# Frozen-String-Literal: TRUE`
+// and `# -*- frozen_string_literal: true -*-` are both `frozen_string_literal: true`
+fn parse_magic_comment(comment: &str) -> Option<(String, String)> {
+    parse_magic_comment_text(comment.strip_prefix('#')?)
+}
+
+fn parse_magic_comment_text(text: &str) -> Option<(String, String)> {
+    let body = text.trim();
+    let body = body
+        .strip_prefix("-*-")
+        .and_then(|b| b.strip_suffix("-*-"))
+        .map(|b| b.trim().trim_end_matches(';'))
+        .unwrap_or(body);
+
+    let (key, value) = body.split_once(':')?;
+    let key = key.trim_end().to_ascii_lowercase().replace('-', "_");
+    let value = value.trim();
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == ';') {
+        return None;
+    }
+
+    let key = match key.as_str() {
+        "coding" | "encoding" => "encoding",
+        "frozen_string_literal"
+        | "warn_indent"
+        | "warn_past_scope"
+        | "shareable_constant_value" => key.as_str(),
+        _ => return None,
+    };
+    Some((key.to_string(), value.to_ascii_lowercase()))
+}
+
+// Every setting in a magic comment, which for an Emacs style comment like
+// `# -*- coding: utf-8; frozen_string_literal: false -*-` can be several,
+// mixed in with editor settings like `mode: ruby`
+fn magic_comment_settings(comment: &str) -> Vec<(String, String)> {
+    comment
+        .strip_prefix('#')
+        .map(magic_comment_text_settings)
+        .unwrap_or_default()
+}
+
+fn magic_comment_text_settings(text: &str) -> Vec<(String, String)> {
+    let body = text.trim();
+    match body.strip_prefix("-*-").and_then(|b| b.strip_suffix("-*-")) {
+        Some(settings) => settings
+            .split(';')
+            .filter_map(parse_magic_comment_text)
+            .collect(),
+        None => parse_magic_comment_text(body).into_iter().collect(),
+    }
+}
+
+/// Whether the text after a comment's `#` is a magic comment
+pub fn is_magic_comment_text(text: &str) -> bool {
+    !magic_comment_text_settings(text).is_empty()
+}
@@ -0,0 +1,46 @@
+use crate::format_options::NumericLiteralStyle;
+
+/// Rewrites an integer, float, rational, or imaginary literal into its
+/// canonical spelling. Only the spelling changes, never the value: radix
+/// prefixes, hex digits, and exponents are lowercased, and long decimal
+/// integers written without separators get them.
+pub fn normalize_numeric_literal(literal: &str, style: NumericLiteralStyle) -> String {
+    // Ripper includes the sign for negative literals, e.g. `-1`
+    let unsigned = literal.trim_start_matches(['-', '+']);
+    let sign = &literal[..literal.len() - unsigned.len()];
+    // `r` and `i` can't be digits in any radix, so whatever's left is the number
+    let number = unsigned.trim_end_matches(['r', 'i']);
+    let suffix = &unsigned[number.len()..];
+
+    let bytes = number.as_bytes();
+    let normalized = if bytes.len() > 2 && bytes[0] == b'0' && bytes[1].is_ascii_alphabetic() {
+        // 0x, 0b, 0o, and 0d
+        number.to_ascii_lowercase()
+    } else if number.contains(['.', 'e', 'E']) {
+        number.to_ascii_lowercase()
+    } else if bytes.len() > 1 && bytes[0] == b'0' {
+        // Octal without the `o`, e.g. `0755`
+        number.to_string()
+    } else {
+        group_digits(number, style)
+    };
+
+    format!("{}{}{}", sign, normalized, suffix)
+}
+
+fn group_digits(digits: &str, style: NumericLiteralStyle) -> String {
+    // Leave any grouping the author chose alone, it might be deliberate
+    // (e.g. `1_0000_0000` or a card number)
+    if digits.contains('_') || style.group_size == 0 || digits.len() < style.min_digits {
+        return digits.to_string();
+    }
+
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / style.group_size);
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx != 0 && (digits.len() - idx) % style.group_size == 0 {
+            grouped.push('_');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
@@ -2,8 +2,10 @@ use crate::comment_block::{CommentBlock, Merge};
 use crate::delimiters::BreakableDelims;
 use crate::file_comments::FileComments;
 use crate::format::{format_inner_string, StringType};
+use crate::format_options::{FormatOptions, MagicCommentOptions, TrailingCommas};
 use crate::heredoc_string::{HeredocKind, HeredocString};
 use crate::line_tokens::*;
+use crate::magic_comments::normalize_magic_comments;
 use crate::render_queue_writer::{RenderQueueWriter, MAX_LINE_LENGTH};
 use crate::render_targets::{
     AbstractTokenTarget, BaseQueue, BreakableCallChainEntry, BreakableEntry,
@@ -12,10 +14,16 @@ use crate::ripper_tree_types::{CallChainElement, StringContentPart};
 use crate::types::{ColNumber, LineNumber};
 use log::debug;
 use std::io::{self, Cursor, Write};
+use std::rc::Rc;
 use std::str;
 
 pub type RenderFunc<'a> = Box<dyn FnOnce(&mut dyn ConcreteParserState) + 'a>;
 
+/// Names that are bound inside blocks without explicit params,
+/// i.e. numbered params and Ruby 3.4's `it`
+const IMPLICIT_BLOCK_PARAMS: [&str; 10] =
+    ["_1", "_2", "_3", "_4", "_5", "_6", "_7", "_8", "_9", "it"];
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FormattingContext {
     Main,
@@ -26,6 +34,8 @@ pub enum FormattingContext {
     ArgsList,
     IfOp,
     StringEmbexpr,
+    // The body of a Sorbet `sig do ... end`
+    SorbetSig,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -85,17 +95,21 @@ where
     fn emit_open_paren(&mut self);
     fn emit_space(&mut self);
     fn emit_comma(&mut self);
+    fn emit_line_continuation(&mut self);
+    fn emit_trailing_comma(&mut self);
     fn emit_end(&mut self);
     fn emit_newline(&mut self);
     fn emit_ident(&mut self, ident: String);
     fn emit_string_content(&mut self, s: String);
     fn emit_double_quote(&mut self);
+    fn emit_single_quote(&mut self);
     fn emit_op(&mut self, op: String);
     fn emit_def(&mut self, def_name: String);
     fn emit_indent(&mut self);
     fn emit_heredoc_start(&mut self, symbol: String, kind: HeredocKind);
     fn emit_after_call_chain(&mut self);
     fn emit_data_end(&mut self);
+    fn emit_access_modifier(&mut self, name: String);
     fn emit_data(&mut self, data: &str);
     fn emit_single_line_delims(&mut self, delims: BreakableDelims);
 
@@ -110,6 +124,8 @@ where
     fn shift_comments_at_index(&mut self, index: usize);
     fn wind_line_forward(&mut self);
     fn render_heredocs(&mut self, skip: bool);
+    // Whether there are heredocs whose bodies go after the next newline
+    fn has_pending_heredocs(&self) -> bool;
     fn push_heredoc_content(
         &mut self,
         symbol: String,
@@ -125,6 +141,7 @@ where
     fn is_absorbing_indents(&self) -> bool;
     fn has_comments_in_line(&self, start_line: LineNumber, end_line: LineNumber) -> bool;
     fn current_line_number(&self) -> u64;
+    fn format_options(&self) -> &FormatOptions;
 
     // blocks
     fn start_indent(&mut self);
@@ -133,9 +150,11 @@ where
     fn end_indent(&mut self);
     fn with_formatting_context(&mut self, fc: FormattingContext, f: RenderFunc);
     fn new_scope(&mut self, f: RenderFunc);
+    fn with_implicit_block_params(&mut self, f: RenderFunc);
     fn new_block(&mut self, f: RenderFunc);
     fn with_start_of_line(&mut self, start_of_line: bool, f: RenderFunc);
     fn breakable_of(&mut self, delims: BreakableDelims, f: RenderFunc);
+    fn multiline_breakable_of(&mut self, delims: BreakableDelims, f: RenderFunc);
     fn inline_breakable_of(&mut self, delims: BreakableDelims, f: RenderFunc);
     fn breakable_call_chain_of(
         &mut self,
@@ -154,6 +173,9 @@ where
     fn will_render_as_multiline(&mut self, f: RenderFunc) -> bool;
 
     fn will_render_beyond_max_line_length(&mut self, f: RenderFunc) -> bool;
+    // An estimate of how long the line being written is so far
+    fn current_line_length(&self) -> usize;
+    fn current_indentation(&self) -> usize;
 
     // stuff to remove from this enum
     fn emit_soft_newline(&mut self);
@@ -177,6 +199,7 @@ pub struct BaseParserState {
     insert_user_newlines: bool,
     spaces_after_last_newline: ColNumber,
     scopes: Vec<Vec<String>>,
+    format_options: Rc<FormatOptions>,
 }
 
 impl ConcreteParserState for BaseParserState {
@@ -186,11 +209,23 @@ impl ConcreteParserState for BaseParserState {
             .expect("it's never empty")
             .contains(&s.to_string())
     }
+    fn format_options(&self) -> &FormatOptions {
+        &self.format_options
+    }
     fn new_scope<'a>(&mut self, f: RenderFunc) {
         self.scopes.push(vec![]);
         f(self);
         self.scopes.pop();
     }
+    fn with_implicit_block_params<'a>(&mut self, f: RenderFunc) {
+        // Blocks share the scope of their enclosing method, but implicit
+        // params are only bound inside the block that uses them
+        let mut scope = self.scopes.last().expect("it's never empty").clone();
+        scope.extend(IMPLICIT_BLOCK_PARAMS.iter().map(|p| p.to_string()));
+        self.scopes.push(scope);
+        f(self);
+        self.scopes.pop();
+    }
     fn bind_variable(&mut self, s: String) {
         self.scopes.last_mut().expect("it's never empty").push(s);
     }
@@ -201,24 +236,29 @@ impl ConcreteParserState for BaseParserState {
         parts: Vec<StringContentPart>,
         end_line: LineNumber,
     ) {
-        let mut next_ps = BaseParserState::render_with_blank_state(self, |n| {
-            n.insert_user_newlines = false;
-            format_inner_string(n, parts, StringType::Heredoc);
-        });
+        let data = match self.format_ruby_heredoc(&symbol, kind, &parts) {
+            Some(formatted) => formatted.into_bytes(),
+            None => {
+                let mut next_ps = BaseParserState::render_with_blank_state(self, |n| {
+                    n.insert_user_newlines = false;
+                    format_inner_string(n, parts, StringType::Heredoc);
+                });
 
-        for hs in next_ps.heredoc_strings.drain(0..) {
-            self.heredoc_strings.push(hs);
-        }
+                for hs in next_ps.heredoc_strings.drain(0..) {
+                    self.heredoc_strings.push(hs);
+                }
+                next_ps.render_to_buffer()
+            }
+        };
 
         // Update line number and clear out any comments we might have rendered in e.g. an embexpr
         //
         // (Ignore this comment extraction, we've already rendered them elsewhere)
-        let _ = self
-            .comments_hash
-            .extract_comments_to_line(self.current_orig_line_number, end_line);
+        let _ =
+            self.comments_hash
+                .extract_comments_to_line(self.current_orig_line_number, end_line, 0);
         self.current_orig_line_number = end_line;
 
-        let data = next_ps.render_to_buffer();
         self.heredoc_strings.push(HeredocString::new(
             symbol,
             kind,
@@ -295,6 +335,23 @@ impl ConcreteParserState for BaseParserState {
             > MAX_LINE_LENGTH
     }
 
+    fn current_line_length(&self) -> usize {
+        let mut length = self.render_queue.current_line_length();
+        for be in &self.breakable_entry_stack {
+            let (be_length, starts_line) = be.current_line_length();
+            if starts_line {
+                length = be_length;
+            } else {
+                length += be_length;
+            }
+        }
+        length
+    }
+
+    fn current_indentation(&self) -> usize {
+        self.current_spaces() as usize
+    }
+
     fn reset_space_count(&mut self) {
         self.spaces_after_last_newline = self.current_spaces();
     }
@@ -331,41 +388,23 @@ impl ConcreteParserState for BaseParserState {
     }
 
     fn breakable_of<'a>(&mut self, delims: BreakableDelims, f: RenderFunc) {
-        self.shift_comments();
-        let mut be = BreakableEntry::new(delims, self.formatting_context.clone());
-        be.push_line_number(self.current_orig_line_number);
-        self.breakable_entry_stack.push(Box::new(be));
-
-        self.new_block(Box::new(|ps| {
-            ps.emit_collapsing_newline();
-            f(ps);
-            ps.emit_collapsing_newline();
-        }));
-
-        // The last newline is in the old block, so we need
-        // to reset to ensure that any comments between now and the
-        // next newline are at the right indentation level
-        self.reset_space_count();
-        // Make sure we shift comments before ending the breakable.
-        // This ensures that comments inside breakables never end up rendering
-        // outside their delimiters.
-        self.shift_comments();
-        self.emit_soft_indent();
+        self.breakable_of_with_multiline(delims, false, f);
+    }
 
-        let insert_be = self
-            .breakable_entry_stack
-            .pop()
-            .expect("cannot have empty here because we just pushed")
-            .to_breakable_entry()
-            .expect("This should be the BreakableEntry we just pushed");
-        self.push_target(ConcreteLineTokenAndTargets::BreakableEntry(insert_be));
+    /// A version of `breakable_of` that's never rendered on a single line
+    fn multiline_breakable_of<'a>(&mut self, delims: BreakableDelims, f: RenderFunc) {
+        self.breakable_of_with_multiline(delims, true, f);
     }
 
     /// A version of `breakable_of` for list-like things that use whitespace delimiters.
     /// At the moment, this is only for conditions in a `when` clause
     fn inline_breakable_of<'a>(&mut self, delims: BreakableDelims, f: RenderFunc) {
         self.shift_comments();
-        let mut be = BreakableEntry::new(delims, self.formatting_context.clone());
+        let mut be = BreakableEntry::new(
+            delims,
+            self.formatting_context.clone(),
+            self.format_options.trailing_commas,
+        );
         be.push_line_number(self.current_orig_line_number);
         self.breakable_entry_stack.push(Box::new(be));
 
@@ -394,8 +433,11 @@ impl ConcreteParserState for BaseParserState {
         f: RenderFunc,
     ) {
         self.shift_comments();
-        let mut be =
-            BreakableCallChainEntry::new(self.formatting_context.clone(), call_chain_elements);
+        let mut be = BreakableCallChainEntry::new(
+            self.formatting_context.clone(),
+            call_chain_elements,
+            self.format_options.call_chains.fill_lines,
+        );
         be.push_line_number(self.current_orig_line_number);
         self.breakable_entry_stack.push(Box::new(be));
 
@@ -452,21 +494,25 @@ impl ConcreteParserState for BaseParserState {
             be.push_line_number(line_number);
         }
 
-        if let Some((comments, last_comment_line)) = self
-            .comments_hash
-            .extract_comments_to_line(self.current_orig_line_number, line_number)
-        {
+        let max_blank_lines = self.max_preserved_blank_lines();
+        if let Some((comments, last_comment_line)) = self.comments_hash.extract_comments_to_line(
+            self.current_orig_line_number,
+            line_number,
+            max_blank_lines,
+        ) {
             self.push_comments(comments);
             self.current_orig_line_number =
                 std::cmp::max(self.current_orig_line_number, last_comment_line);
         }
 
         debug!("lns: {} {}", line_number, self.current_orig_line_number);
-        if line_difference_requires_newline(line_number, self.current_orig_line_number)
-            && self.insert_user_newlines
-        {
-            debug!("extra line");
-            self.insert_extra_newline_at_last_newline();
+        if self.insert_user_newlines {
+            for _ in
+                0..blank_lines_between(line_number, self.current_orig_line_number, max_blank_lines)
+            {
+                debug!("extra line");
+                self.insert_extra_newline_at_last_newline();
+            }
         }
 
         self.current_orig_line_number = line_number;
@@ -518,6 +564,10 @@ impl ConcreteParserState for BaseParserState {
         self.push_concrete_token(ConcreteLineToken::DoubleQuote);
     }
 
+    fn emit_single_quote(&mut self) {
+        self.push_concrete_token(ConcreteLineToken::SingleQuote);
+    }
+
     fn emit_string_content(&mut self, s: String) {
         let newline_count = s.matches('\n').count() as u64;
         self.current_orig_line_number += newline_count;
@@ -613,6 +663,22 @@ impl ConcreteParserState for BaseParserState {
         self.push_concrete_token(ConcreteLineToken::Comma);
     }
 
+    fn emit_line_continuation(&mut self) {
+        if !self.breakable_entry_stack.is_empty() {
+            self.push_abstract_token(AbstractLineToken::LineContinuation);
+        }
+    }
+
+    fn emit_trailing_comma(&mut self) {
+        // Whether this becomes a comma is up to the breakable we're in, so
+        // there's nothing to do outside of one (or when they're turned off)
+        if self.format_options.trailing_commas != TrailingCommas::Never
+            && !self.breakable_entry_stack.is_empty()
+        {
+            self.push_abstract_token(AbstractLineToken::TrailingComma);
+        }
+    }
+
     fn shift_comments(&mut self) {
         let idx_of_prev_hard_newline = self.index_of_prev_hard_newline();
         self.shift_comments_at_index(idx_of_prev_hard_newline.unwrap_or(0));
@@ -748,6 +814,10 @@ impl ConcreteParserState for BaseParserState {
         self.push_concrete_token(ConcreteLineToken::DataEnd);
     }
 
+    fn emit_access_modifier(&mut self, name: String) {
+        self.push_concrete_token(ConcreteLineToken::AccessModifier { name });
+    }
+
     fn emit_data(&mut self, data: &str) {
         self.push_concrete_token(ConcreteLineToken::DirectPart {
             part: data.to_string(),
@@ -792,6 +862,10 @@ impl ConcreteParserState for BaseParserState {
         });
     }
 
+    fn has_pending_heredocs(&self) -> bool {
+        !self.heredoc_strings.is_empty()
+    }
+
     fn render_heredocs(&mut self, skip: bool) {
         while !self.heredoc_strings.is_empty() {
             let next_heredoc = self.heredoc_strings.pop().expect("we checked it's there");
@@ -845,7 +919,7 @@ impl ConcreteParserState for BaseParserState {
 }
 
 impl BaseParserState {
-    pub fn new(fc: FileComments) -> Self {
+    pub fn new(fc: FileComments, format_options: FormatOptions) -> Self {
         BaseParserState {
             depth_stack: vec![IndentDepth::new()],
             start_of_line: vec![true],
@@ -861,6 +935,7 @@ impl BaseParserState {
             insert_user_newlines: true,
             spaces_after_last_newline: 0,
             scopes: vec![vec![]],
+            format_options: Rc::new(format_options),
         }
     }
 
@@ -900,6 +975,13 @@ impl BaseParserState {
         }
     }
 
+    // Blank lines the author wrote are kept up to the configured maximum. We
+    // keep one even when that's 0, since the blank lines rubyfmt adds itself
+    // (e.g. after `end`) rely on it, and they're removed again at the end
+    fn max_preserved_blank_lines(&self) -> LineNumber {
+        std::cmp::max(self.format_options.blank_lines.max_consecutive, 1) as LineNumber
+    }
+
     fn insert_extra_newline_at_last_newline(&mut self) {
         let idx = self.index_of_prev_hard_newline();
         let insert_idx = idx.unwrap_or(0);
@@ -947,8 +1029,9 @@ impl BaseParserState {
     // Creates a copy of the parser state *with the depth_stack reset*.
     // This is used for heredocs, where we explicitly want to ignore current indentation.
     fn new_with_reset_depth_stack(ps: &BaseParserState) -> Self {
-        let mut next_ps = BaseParserState::new(FileComments::default());
+        let mut next_ps = BaseParserState::new(FileComments::default(), FormatOptions::default());
         next_ps.comments_hash = ps.comments_hash.clone();
+        next_ps.format_options = Rc::clone(&ps.format_options);
         next_ps.start_of_line = ps.start_of_line.clone();
         next_ps.current_orig_line_number = ps.current_orig_line_number;
         next_ps
@@ -962,7 +1045,8 @@ impl BaseParserState {
     }
 
     pub fn write<W: Write>(self, writer: &mut W) -> io::Result<()> {
-        let rqw = RenderQueueWriter::new(self.consume_to_render_queue());
+        let format_options = Rc::clone(&self.format_options);
+        let rqw = RenderQueueWriter::new(self.consume_to_render_queue(), format_options);
         rqw.write(writer)
     }
 
@@ -981,21 +1065,50 @@ impl BaseParserState {
         }
     }
 
-    pub fn flush_start_of_file_comments(&mut self) {
-        match self
+    pub fn flush_start_of_file_comments(&mut self, program_has_code: bool) {
+        let mut comments = self
             .comments_hash
-            .take_start_of_file_contiguous_comment_lines()
-        {
+            .take_start_of_file_contiguous_comment_lines();
+        let len = comments.as_ref().map(CommentBlock::len).unwrap_or(0);
+
+        let mut magic_comments = self.format_options.magic_comments;
+        // Files without any code don't need either of these
+        magic_comments.add_frozen_string_literal &= program_has_code;
+        let mut blank_line_after = false;
+        if magic_comments != MagicCommentOptions::default() {
+            let mut lines = normalize_magic_comments(
+                comments
+                    .map(CommentBlock::into_comments)
+                    .unwrap_or_default(),
+                magic_comments,
+            );
+            if lines.last().map(String::is_empty).unwrap_or(false) {
+                lines.pop();
+                blank_line_after = program_has_code;
+            }
+            comments = if lines.is_empty() {
+                None
+            } else {
+                Some(CommentBlock::new(1..lines.len() as LineNumber + 1, lines))
+            };
+        }
+
+        match comments {
             None => {
                 self.on_line(1);
             }
             Some(comments) => {
-                let len = comments.len();
                 let lts = comments.into_line_tokens();
                 for comment in lts.into_iter() {
                     self.push_concrete_token(comment);
                 }
                 self.current_orig_line_number = len as LineNumber;
+                if blank_line_after {
+                    self.push_concrete_token(ConcreteLineToken::HardNewLine);
+                    // Stands in for a blank line the author might've left, so
+                    // it isn't added a second time
+                    self.current_orig_line_number += 1;
+                }
                 debug!("rq: {:?}", self.render_queue);
             }
         }
@@ -1047,6 +1160,87 @@ impl BaseParserState {
         self.push_concrete_token(ConcreteLineToken::HeredocClose { symbol });
     }
 
+    /// The formatted body of a `<<~` heredoc that holds Ruby code, or `None`
+    /// to leave it as it is. That's the case when it has interpolation, when
+    /// it doesn't parse, or when it has escapes, since formatting (e.g.
+    /// changing a string's quotes) could add or remove escapes, and those mean
+    /// something different inside a heredoc that isn't single quoted.
+    fn format_ruby_heredoc(
+        &self,
+        symbol: &str,
+        kind: HeredocKind,
+        parts: &[StringContentPart],
+    ) -> Option<String> {
+        let identifier = symbol.trim_matches(['\'', '"']);
+        if !kind.is_squiggly() || !self.format_options.ruby_heredocs.contains(identifier) {
+            return None;
+        }
+
+        let mut source = String::new();
+        for part in parts {
+            match part {
+                StringContentPart::TStringContent(t) => source.push_str(&t.1),
+                _ => return None,
+            }
+        }
+
+        let options = FormatOptions {
+            // The body isn't the top of a file
+            magic_comments: MagicCommentOptions::default(),
+            ..(*self.format_options).clone()
+        };
+        let formatted = crate::format_buffer_with_options(&source, &options).ok()?;
+
+        let single_quoted = symbol.starts_with('\'');
+        if !single_quoted && (source.contains('\\') || formatted.contains('\\')) {
+            return None;
+        }
+        Some(formatted.trim_end_matches('\n').to_string())
+    }
+
+    fn breakable_of_with_multiline(
+        &mut self,
+        delims: BreakableDelims,
+        always_multiline: bool,
+        f: RenderFunc,
+    ) {
+        self.shift_comments();
+        let mut be = BreakableEntry::new(
+            delims,
+            self.formatting_context.clone(),
+            self.format_options.trailing_commas,
+        );
+        be.push_line_number(self.current_orig_line_number);
+        self.breakable_entry_stack.push(Box::new(be));
+
+        self.new_block(Box::new(|ps| {
+            ps.emit_collapsing_newline();
+            f(ps);
+            ps.emit_collapsing_newline();
+        }));
+
+        // The last newline is in the old block, so we need
+        // to reset to ensure that any comments between now and the
+        // next newline are at the right indentation level
+        self.reset_space_count();
+        // Make sure we shift comments before ending the breakable.
+        // This ensures that comments inside breakables never end up rendering
+        // outside their delimiters.
+        self.shift_comments();
+        self.emit_soft_indent();
+
+        let mut insert_be = self
+            .breakable_entry_stack
+            .pop()
+            .expect("cannot have empty here because we just pushed")
+            .to_breakable_entry()
+            .expect("This should be the BreakableEntry we just pushed");
+        if always_multiline {
+            insert_be.set_always_multiline();
+        }
+        self.push_target(ConcreteLineTokenAndTargets::BreakableEntry(insert_be));
+    }
+
     fn render_with_blank_state<F>(ps: &mut BaseParserState, f: F) -> BaseParserState
     where
         F: FnOnce(&mut BaseParserState),
@@ -1057,6 +1251,15 @@ impl BaseParserState {
     }
 }
 
-pub fn line_difference_requires_newline(to_line: LineNumber, from_line: LineNumber) -> bool {
-    (to_line > from_line) && (to_line - from_line >= 2)
+/// How many of the blank lines between two lines to keep, which is at most `max`
+pub fn blank_lines_between(
+    to_line: LineNumber,
+    from_line: LineNumber,
+    max: LineNumber,
+) -> LineNumber {
+    if to_line > from_line {
+        std::cmp::min(to_line - from_line - 1, max)
+    } else {
+        0
+    }
 }
@@ -1,3 +1,7 @@
+use crate::blank_lines::normalize_blank_lines;
+use crate::comment_reflow::reflow_comments;
+use crate::doc_examples::format_doc_examples;
+use crate::format_options::{BlankLineOptions, FormatOptions};
 use crate::heredoc_string::HeredocKind;
 use crate::intermediary::{BlanklineReason, Intermediary};
 use crate::line_tokens::*;
@@ -7,26 +11,48 @@ use crate::render_targets::{
 #[cfg(debug_assertions)]
 use log::debug;
 use std::io::{self, Write};
+use std::rc::Rc;
 
 pub const MAX_LINE_LENGTH: usize = 120;
 
 pub struct RenderQueueWriter {
     tokens: Vec<ConcreteLineTokenAndTargets>,
+    format_options: Rc<FormatOptions>,
 }
 
 impl RenderQueueWriter {
-    pub fn new(tokens: Vec<ConcreteLineTokenAndTargets>) -> Self {
-        RenderQueueWriter { tokens }
+    pub fn new(
+        tokens: Vec<ConcreteLineTokenAndTargets>,
+        format_options: Rc<FormatOptions>,
+    ) -> Self {
+        RenderQueueWriter {
+            tokens,
+            format_options,
+        }
     }
 
     pub fn write<W: Write>(self, writer: &mut W) -> io::Result<()> {
-        let mut accum = Intermediary::new();
+        let mut accum = Intermediary::new(
+            std::cmp::max(self.format_options.blank_lines.max_consecutive, 1),
+            self.format_options.sort_requires,
+        );
         #[cfg(debug_assertions)]
         {
             debug!("first tokens {:?}", self.tokens);
         }
         Self::render_as(&mut accum, self.tokens);
-        Self::write_final_tokens(writer, accum.into_tokens())
+        let mut tokens = accum.into_tokens();
+        if self.format_options.format_doc_examples {
+            tokens = format_doc_examples(tokens, &self.format_options);
+        }
+        if self.format_options.reflow_comments {
+            tokens = reflow_comments(tokens);
+        }
+        // The defaults are what the intermediary already does
+        if self.format_options.blank_lines != BlankLineOptions::default() {
+            tokens = normalize_blank_lines(tokens, self.format_options.blank_lines);
+        }
+        Self::write_final_tokens(writer, tokens)
     }
 
     fn render_as(accum: &mut Intermediary, tokens: Vec<ConcreteLineTokenAndTargets>) {
@@ -223,14 +249,53 @@ impl RenderQueueWriter {
         if must_multiline
             || ((length > MAX_LINE_LENGTH || bcce.is_multiline()) && !bcce.in_string_embexpr())
         {
-            let tokens = bcce.into_tokens(ConvertType::MultiLine);
-            Self::render_as(accum, tokens);
+            if bcce.fills_lines() {
+                Self::render_call_chain_filling_lines(accum, bcce);
+            } else {
+                let tokens = bcce.into_tokens(ConvertType::MultiLine);
+                Self::render_as(accum, tokens);
+            }
         } else {
             bcce.remove_call_chain_magic_tokens();
             Self::render_as(accum, bcce.into_tokens(ConvertType::SingleLine));
         }
     }
 
+    // Only starts a new line for a call when it doesn't fit on the current one
+    fn render_call_chain_filling_lines(accum: &mut Intermediary, bcce: BreakableCallChainEntry) {
+        for mut call in bcce.into_calls() {
+            let line_break = match call.first() {
+                Some(AbstractLineToken::CollapsingNewLine(heredocs)) => {
+                    let call_length = call[2..]
+                        .iter()
+                        .flat_map(|t| t.clone().into_single_line())
+                        .map(|t| t.into_ruby())
+                        .collect::<String>()
+                        .split('\n')
+                        .next()
+                        .map(str::len)
+                        .unwrap_or(0);
+                    let fits = heredocs.is_none()
+                        && accum.current_line_length() + call_length <= MAX_LINE_LENGTH;
+                    let line_break = call.drain(..2).flat_map(|t| {
+                        if fits {
+                            t.into_single_line()
+                        } else {
+                            t.into_multi_line()
+                        }
+                    });
+                    line_break.collect()
+                }
+                _ => Vec::new(),
+            };
+            Self::render_as(accum, line_break);
+            Self::render_as(
+                accum,
+                call.into_iter().flat_map(|t| t.into_multi_line()).collect(),
+            );
+        }
+    }
+
     fn write_final_tokens<W: Write>(
         writer: &mut W,
         mut tokens: Vec<ConcreteLineToken>,
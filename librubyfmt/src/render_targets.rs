@@ -1,4 +1,5 @@
 use crate::delimiters::BreakableDelims;
+use crate::format_options::TrailingCommas;
 use crate::line_tokens::{AbstractLineToken, ConcreteLineToken, ConcreteLineTokenAndTargets};
 use crate::parser_state::FormattingContext;
 use crate::ripper_tree_types::{CallChainElement, Expression, StringLiteral};
@@ -44,6 +45,14 @@ impl BaseQueue {
             .iter()
             .rposition(|v| v.is_newline() || v.is_comment())
     }
+
+    pub fn current_line_length(&self) -> usize {
+        let line_start = self.index_of_prev_newline().map(|i| i + 1).unwrap_or(0);
+        self.tokens[line_start..]
+            .iter()
+            .map(|t| t.clone().into_ruby().len())
+            .sum()
+    }
 }
 
 pub trait AbstractTokenTarget: std::fmt::Debug {
@@ -62,6 +71,26 @@ pub trait AbstractTokenTarget: std::fmt::Debug {
         self.tokens().len()
     }
 
+    // The length of the last line in this target, and whether that line
+    // started in it. Anything that could still break counts as one line,
+    // except that a soft newline is taken to be a real one.
+    fn current_line_length(&self) -> (usize, bool) {
+        let line_start = self.tokens().iter().rposition(|t| t.is_newline());
+        let length = self.tokens()[line_start.map(|i| i + 1).unwrap_or(0)..]
+            .iter()
+            .map(|t| match t {
+                AbstractLineToken::SoftIndent { depth } => *depth as usize,
+                t => t
+                    .clone()
+                    .into_single_line()
+                    .into_iter()
+                    .map(|t| t.into_ruby().len())
+                    .sum(),
+            })
+            .sum();
+        (length, line_start.is_some())
+    }
+
     fn index_of_prev_newline(&self) -> Option<usize> {
         let first_idx = self
             .tokens()
@@ -95,6 +124,8 @@ pub struct BreakableEntry {
     line_numbers: HashSet<LineNumber>,
     delims: BreakableDelims,
     context: Vec<FormattingContext>,
+    trailing_comma: bool,
+    always_multiline: bool,
 }
 
 impl AbstractTokenTarget for BreakableEntry {
@@ -117,10 +148,18 @@ impl AbstractTokenTarget for BreakableEntry {
     fn into_tokens(self, ct: ConvertType) -> Vec<ConcreteLineTokenAndTargets> {
         match ct {
             ConvertType::MultiLine => {
+                let trailing_comma = self.trailing_comma;
                 let mut new_tokens: Vec<_> = self
                     .tokens
                     .into_iter()
-                    .flat_map(|t| t.into_multi_line())
+                    .flat_map(|t| match t {
+                        AbstractLineToken::TrailingComma if trailing_comma => {
+                            vec![ConcreteLineTokenAndTargets::ConcreteLineToken(
+                                ConcreteLineToken::Comma,
+                            )]
+                        }
+                        t => t.into_multi_line(),
+                    })
                     .collect();
                 new_tokens.insert(0, self.delims.multi_line_open().into());
                 new_tokens.push(self.delims.multi_line_close().into());
@@ -154,7 +193,8 @@ impl AbstractTokenTarget for BreakableEntry {
     }
 
     fn is_multiline(&self) -> bool {
-        self.line_numbers.len() > 1
+        self.always_multiline
+            || self.line_numbers.len() > 1
             || self.any_collapsing_newline_has_heredoc_content()
             || self.contains_hard_newline()
     }
@@ -176,15 +216,25 @@ impl AbstractTokenTarget for BreakableEntry {
 }
 
 impl BreakableEntry {
-    pub fn new(delims: BreakableDelims, context: Vec<FormattingContext>) -> Self {
+    pub fn new(
+        delims: BreakableDelims,
+        context: Vec<FormattingContext>,
+        trailing_commas: TrailingCommas,
+    ) -> Self {
         BreakableEntry {
             tokens: Vec::new(),
             line_numbers: HashSet::new(),
+            trailing_comma: delims.accepts_trailing_comma(trailing_commas),
             delims,
             context,
+            always_multiline: false,
         }
     }
 
+    pub fn set_always_multiline(&mut self) {
+        self.always_multiline = true;
+    }
+
     pub fn in_string_embexpr(&self) -> bool {
         self.context
             .iter()
@@ -207,6 +257,7 @@ pub struct BreakableCallChainEntry {
     line_numbers: HashSet<LineNumber>,
     call_chain: Vec<CallChainElement>,
     context: Vec<FormattingContext>,
+    fill_lines: bool,
 }
 
 impl AbstractTokenTarget for BreakableCallChainEntry {
@@ -331,12 +382,13 @@ impl AbstractTokenTarget for BreakableCallChainEntry {
             Some(CallChainElement::Expression(expr)) => !expr.is_constant_reference(),
             _ => false,
         };
-        let has_comments = self.tokens.iter().any(|t| {
-            matches!(
-                t,
-                AbstractLineToken::ConcreteLineToken(ConcreteLineToken::Comment { .. })
-            )
-        });
+        // Filling lines only breaks the chain where it doesn't fit, no
+        // matter how it was written
+        if self.fills_lines() {
+            return false;
+        }
+
+        let has_comments = self.has_comments();
 
         // If the first item in the chain is a multiline expression (like a hash or array),
         // ignore it when checking line length.
@@ -384,13 +436,50 @@ impl AbstractTokenTarget for BreakableCallChainEntry {
 }
 
 impl BreakableCallChainEntry {
-    pub fn new(context: Vec<FormattingContext>, call_chain: Vec<CallChainElement>) -> Self {
+    pub fn new(
+        context: Vec<FormattingContext>,
+        call_chain: Vec<CallChainElement>,
+        fill_lines: bool,
+    ) -> Self {
         BreakableCallChainEntry {
             tokens: Vec::new(),
             line_numbers: HashSet::new(),
             context,
             call_chain,
+            fill_lines,
+        }
+    }
+
+    /// Whether the calls should be put on as few lines as they fit on, rather
+    /// than one per line. Chains with comments always get one call per line,
+    /// since nothing can go on a line after a comment.
+    pub fn fills_lines(&self) -> bool {
+        self.fill_lines && !self.has_comments()
+    }
+
+    fn has_comments(&self) -> bool {
+        self.tokens.iter().any(|t| {
+            matches!(
+                t,
+                AbstractLineToken::ConcreteLineToken(ConcreteLineToken::Comment { .. })
+            )
+        })
+    }
+
+    /// Splits the chain's tokens up by call, every part but the first starts
+    /// with the newline and indentation it gets when it's on a line of its own
+    pub fn into_calls(self) -> Vec<Vec<AbstractLineToken>> {
+        let mut calls = vec![Vec::new()];
+        let mut tokens = self.tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            if matches!(token, AbstractLineToken::CollapsingNewLine(_))
+                && matches!(tokens.peek(), Some(AbstractLineToken::SoftIndent { .. }))
+            {
+                calls.push(Vec::new());
+            }
+            calls.last_mut().expect("never empty").push(token);
         }
+        calls
     }
 
     /// Removes `BeginCallChainIndent` and `EndCallChainIndent`, which is only really
@@ -0,0 +1,158 @@
+use crate::line_metadata::LineMetadata;
+use crate::line_tokens::{join_lines, ConcreteLineToken};
+use std::ops::Range;
+
+pub enum RequireLineKind {
+    Comment,
+    Require(RequireCall),
+    Other,
+}
+
+pub struct RequireCall {
+    depth: u32,
+    relative: bool,
+    // Without the quotes
+    path: String,
+}
+
+/// A block of consecutive single line `require` and `require_relative`
+/// calls at the same indentation, collected by the intermediary as each line
+/// is finished. Comment lines directly above a call move with it, and
+/// anything else (including a blank line) ends the block, so files where load
+/// order matters can keep it by adding a blank line.
+#[derive(Default)]
+pub struct RequireBlock {
+    // Each call with the range of tokens for its line and the comments above
+    // it, not including the newline at the end
+    entries: Vec<(RequireCall, Range<usize>)>,
+    // Where the comment lines since the last call start
+    comments_start: Option<usize>,
+}
+
+impl RequireBlock {
+    /// Whether `kind` can go in this block, otherwise the block has to be
+    /// finished first
+    pub fn accepts(&self, kind: &RequireLineKind) -> bool {
+        match kind {
+            RequireLineKind::Comment => true,
+            RequireLineKind::Require(call) => match self.entries.first() {
+                Some((first, _)) => first.depth == call.depth,
+                None => true,
+            },
+            RequireLineKind::Other => false,
+        }
+    }
+
+    pub fn push(&mut self, kind: RequireLineKind, line: Range<usize>) {
+        match kind {
+            RequireLineKind::Comment => {
+                self.comments_start.get_or_insert(line.start);
+            }
+            RequireLineKind::Require(call) => {
+                let start = self.comments_start.take().unwrap_or(line.start);
+                self.entries.push((call, start..line.end));
+            }
+            RequireLineKind::Other => {}
+        }
+    }
+
+    pub fn has_calls(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    /// Whether any of the calls is a plain `require`, which the intermediary
+    /// always puts a blank line after
+    pub fn has_require(&self) -> bool {
+        self.entries.iter().any(|(call, _)| !call.relative)
+    }
+
+    /// Where the comments after the last call start, if there are any
+    pub fn trailing_comments_start(&self) -> Option<usize> {
+        self.comments_start
+    }
+
+    /// Sorts the calls in place, `require`s first, `tokens` must be the ones
+    /// the ranges were taken from. The sorted lines take up exactly the same
+    /// tokens, so nothing after the block moves.
+    pub fn sort(&self, tokens: &mut Vec<ConcreteLineToken>) {
+        let (first, last) = match (self.entries.first(), self.entries.last()) {
+            (Some((_, first)), Some((_, last))) if self.entries.len() > 1 => (first, last),
+            _ => return,
+        };
+
+        let mut entries = self
+            .entries
+            .iter()
+            .map(|(call, range)| ((call.relative, &call.path), tokens[range.clone()].to_vec()))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(key, _)| *key);
+
+        let sorted = join_lines(entries.into_iter().map(|(_, lines)| lines).collect());
+        tokens.splice(first.start..last.end, sorted);
+    }
+}
+
+pub fn classify_line(line: &[ConcreteLineToken], metadata: &LineMetadata) -> RequireLineKind {
+    let (depth, rest) = match line.split_first() {
+        Some((ConcreteLineToken::Indent { depth }, rest)) => (*depth, rest),
+        _ => (0, line),
+    };
+
+    if metadata.has_require() || metadata.has_require_relative() {
+        // The first token is the method name
+        return match rest
+            .split_first()
+            .and_then(|(_, args)| single_string_arg(args))
+        {
+            Some(path) => RequireLineKind::Require(RequireCall {
+                depth,
+                relative: metadata.has_require_relative(),
+                path,
+            }),
+            None => RequireLineKind::Other,
+        };
+    }
+
+    match rest {
+        // Empty comments are blank lines
+        [ConcreteLineToken::Comment { contents }] if !contents.is_empty() => {
+            RequireLineKind::Comment
+        }
+        _ => RequireLineKind::Other,
+    }
+}
+
+// Anything more complicated (e.g. `require "a" if b`, an interpolated path
+// or an arg that continues on the next line) isn't worth the risk of moving
+fn single_string_arg(args: &[ConcreteLineToken]) -> Option<String> {
+    let mut rendered = String::new();
+    for token in args {
+        match token {
+            ConcreteLineToken::Space
+            | ConcreteLineToken::OpenParen
+            | ConcreteLineToken::CloseParen
+            | ConcreteLineToken::Delim { .. }
+            | ConcreteLineToken::DoubleQuote
+            | ConcreteLineToken::SingleQuote
+            | ConcreteLineToken::LTStringContent { .. } => {
+                rendered.push_str(&token.clone().into_ruby())
+            }
+            _ => return None,
+        }
+    }
+
+    let rendered = rendered.trim();
+    let literal = rendered
+        .strip_prefix('(')
+        .and_then(|r| r.strip_suffix(')'))
+        .unwrap_or(rendered);
+    let quote = match literal.chars().next() {
+        Some(q @ ('"' | '\'')) => q,
+        _ => return None,
+    };
+    let path = literal.strip_prefix(quote)?.strip_suffix(quote)?;
+    if path.contains(quote) {
+        return None;
+    }
+    Some(path.to_string())
+}
@@ -77,7 +77,7 @@ pub struct Program(pub program_tag, pub Vec<Expression>);
 
 def_tag!(undeserializable, "oiqjweoifjqwoeifjwqoiefjqwoiej");
 #[derive(Deserialize, Debug, Clone)]
-pub struct ToProc(pub undeserializable, pub Box<Expression>);
+pub struct ToProc(pub undeserializable, pub Option<Box<Expression>>);
 
 #[derive(RipperDeserialize, Debug, Clone)]
 pub enum Expression {
@@ -226,7 +226,6 @@ impl Expression {
             | Expression::Rational(Rational(.., linecol)) => Some(linecol.0),
             // Expressions with locations defined by nested expressions
             Expression::RescueMod(RescueMod(_, expr, _))
-            | Expression::ToProc(ToProc(_, expr))
             | Expression::Unary(Unary(.., expr))
             | Expression::ConstPathRef(ConstPathRef(_, expr, ..))
             | Expression::Defined(Defined(.., expr))
@@ -240,6 +239,9 @@ impl Expression {
             | Expression::IfOp(IfOp(_, expr, ..)) => expr.start_line(),
             // Miscellaneous expressions with special cases
             Expression::VoidStmt(..) => None,
+            Expression::ToProc(ToProc(_, maybe_expr)) => {
+                maybe_expr.as_ref().and_then(|expr| expr.start_line())
+            }
             Expression::Paren(ParenExpr(.., paren_expr, _)) => paren_expr.start_line(),
             Expression::MLhs(MLhs(mlhs_inners)) => {
                 mlhs_inners.first().and_then(|mlhs| mlhs.start_line())
@@ -733,7 +735,8 @@ pub struct HeredocStringLiteral(
 def_tag!(string_literal_tag, "string_literal");
 #[derive(RipperDeserialize, Debug, Clone)]
 pub enum StringLiteral {
-    Normal(string_literal_tag, StringContent, StartEnd),
+    // The delimiter is the opening quote, e.g. `'` or `%q(`
+    Normal(string_literal_tag, StringContent, Option<String>, StartEnd),
     Heredoc(string_literal_tag, HeredocStringLiteral, StringContent),
 }
 
@@ -755,6 +758,8 @@ def_tag!(dyna_symbol_tag, "dyna_symbol");
 pub struct DynaSymbol(
     pub dyna_symbol_tag,
     pub StringContentOrStringContentParts,
+    // The closing quote, e.g. `'` for `:'foo'`
+    Option<String>,
     StartEnd,
 );
 
@@ -762,12 +767,13 @@ impl DynaSymbol {
     pub fn to_string_literal(self) -> StringLiteral {
         match self.1 {
             StringContentOrStringContentParts::StringContent(sc) => {
-                StringLiteral::Normal(string_literal_tag, sc, self.2)
+                StringLiteral::Normal(string_literal_tag, sc, self.2, self.3)
             }
             StringContentOrStringContentParts::StringContentParts(scp) => StringLiteral::Normal(
                 string_literal_tag,
                 StringContent(string_content_tag, scp),
                 self.2,
+                self.3,
             ),
         }
     }
@@ -899,7 +905,8 @@ def_tag!(args_add_star_tag, "args_add_star");
 pub struct ArgsAddStar(
     pub args_add_star_tag,
     pub Box<ArgsAddStarOrExpressionListOrArgsForward>,
-    pub Box<Expression>,
+    // `None` for Ruby 3.2's anonymous splat forwarding, e.g. `foo(*)`
+    pub Option<Box<Expression>>,
     pub Vec<Expression>,
 );
 
@@ -1298,7 +1305,7 @@ pub struct KwRestParam(pub kw_rest_param_tag, pub Option<Ident>);
 
 def_tag!(blockarg_tag, "blockarg");
 #[derive(Deserialize, Debug, Clone)]
-pub struct BlockArg(pub blockarg_tag, pub Ident);
+pub struct BlockArg(pub blockarg_tag, pub Option<Ident>);
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct LineCol(pub LineNumber, pub u64);
@@ -1350,26 +1357,26 @@ pub fn normalize_args_add_block_or_expression_list(
 pub fn normalize_args_add_block(aab: ArgsAddBlock) -> ArgsAddStarOrExpressionListOrArgsForward {
     // .1 is expression list
     // .2 is block
-    match aab.2 {
-        ToProcExpr::NotPresent(_) => (aab.1).into_args_add_star_or_expression_list(),
-        ToProcExpr::Present(e) => {
-            let trailing_expr_as_vec = vec![Expression::ToProc(ToProc(undeserializable, e))];
-
-            match (aab.1).into_args_add_star_or_expression_list() {
-                ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(items) => {
-                    ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(
-                        vec![items, trailing_expr_as_vec].concat(),
-                    )
-                }
-                ArgsAddStarOrExpressionListOrArgsForward::ArgsAddStar(aas) => {
-                    let mut new_aas = aas;
-                    new_aas.3 = vec![new_aas.3, trailing_expr_as_vec].concat();
-                    ArgsAddStarOrExpressionListOrArgsForward::ArgsAddStar(new_aas)
-                }
-                ArgsAddStarOrExpressionListOrArgsForward::ArgsForward(af) => {
-                    ArgsAddStarOrExpressionListOrArgsForward::ArgsForward(af)
-                }
-            }
+    let block = match aab.2 {
+        ToProcExpr::NotPresent(_) => return (aab.1).into_args_add_star_or_expression_list(),
+        ToProcExpr::Present(e) => Some(e),
+        ToProcExpr::Anonymous => None,
+    };
+    let trailing_expr_as_vec = vec![Expression::ToProc(ToProc(undeserializable, block))];
+
+    match (aab.1).into_args_add_star_or_expression_list() {
+        ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(items) => {
+            ArgsAddStarOrExpressionListOrArgsForward::ExpressionList(
+                vec![items, trailing_expr_as_vec].concat(),
+            )
+        }
+        ArgsAddStarOrExpressionListOrArgsForward::ArgsAddStar(aas) => {
+            let mut new_aas = aas;
+            new_aas.3 = vec![new_aas.3, trailing_expr_as_vec].concat();
+            ArgsAddStarOrExpressionListOrArgsForward::ArgsAddStar(new_aas)
+        }
+        ArgsAddStarOrExpressionListOrArgsForward::ArgsForward(af) => {
+            ArgsAddStarOrExpressionListOrArgsForward::ArgsForward(af)
         }
     }
 }
@@ -1412,6 +1419,8 @@ pub struct ArgParen(pub arg_paren_tag, pub Box<ArgNode>, pub StartEnd);
 pub enum ToProcExpr {
     NotPresent(bool),
     Present(Box<Expression>),
+    // Ruby 3.1's anonymous block forwarding, e.g. `foo(&)`, is a bare `nil`
+    Anonymous,
 }
 
 // ArgsAddBlock
@@ -1485,18 +1494,24 @@ impl AssocNewOrAssocSplat {
     pub fn start_line(&self) -> Option<u64> {
         match self {
             AssocNewOrAssocSplat::AssocNew(assoc_new) => assoc_new.as_ref().1.start_line(),
-            AssocNewOrAssocSplat::AssocSplat(assoc_splat) => assoc_splat.as_ref().1.start_line(),
+            AssocNewOrAssocSplat::AssocSplat(assoc_splat) => assoc_splat
+                .as_ref()
+                .1
+                .as_ref()
+                .and_then(|expr| expr.start_line()),
         }
     }
 }
 
 def_tag!(assoc_new_tag, "assoc_new");
+// The value is `None` for Ruby 3.1's shorthand hash syntax, e.g. `{x:, y:}`
 #[derive(Deserialize, Debug, Clone)]
 pub struct AssocNew(pub assoc_new_tag, pub AssocKey, pub Option<Expression>);
 
 def_tag!(assoc_splat_tag, "assoc_splat");
+// `None` for Ruby 3.2's anonymous keyword splat forwarding, e.g. `foo(**)`
 #[derive(Deserialize, Debug, Clone)]
-pub struct AssocSplat(pub assoc_splat_tag, pub Expression);
+pub struct AssocSplat(pub assoc_splat_tag, pub Option<Expression>);
 
 #[derive(RipperDeserialize, Debug, Clone)]
 pub enum AssocKey {
@@ -2300,6 +2315,14 @@ pub struct DoBlock(
     pub StartEnd,
 );
 
+impl DoBlock {
+    /// Only for blocks without `rescue`, `else`, or `ensure`, which braces
+    /// can't have
+    pub fn into_brace_block(self) -> BraceBlock {
+        BraceBlock(brace_block_tag, self.1, self.2 .1, self.3)
+    }
+}
+
 def_tag!(brace_block_tag, "brace_block");
 #[derive(Deserialize, Debug, Clone)]
 pub struct BraceBlock(
@@ -2309,6 +2332,17 @@ pub struct BraceBlock(
     pub StartEnd,
 );
 
+impl BraceBlock {
+    pub fn into_do_block(self) -> DoBlock {
+        DoBlock(
+            do_block_tag,
+            self.1,
+            Box::new(BodyStmt(bodystmt_tag, self.2, None, None, None)),
+            self.3,
+        )
+    }
+}
+
 def_tag!(while_tag, "while");
 #[derive(Deserialize, Debug, Clone)]
 pub struct While(
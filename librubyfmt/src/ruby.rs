@@ -84,6 +84,8 @@ extern "C" {
     pub fn rb_id2name(id: ID) -> *const libc::c_char;
     pub fn rb_ary_entry(arr: VALUE, idx: libc::c_long) -> VALUE;
     pub fn rb_raise(cls: VALUE, msg: *const libc::c_char);
+    pub fn rb_gc_register_address(addr: *mut VALUE);
+    pub fn rb_gc_unregister_address(addr: *mut VALUE);
     pub fn rb_block_call(
         obj: VALUE,
         method_id: ID,
@@ -139,6 +141,32 @@ pub fn debug_inspect(v: VALUE) {
     }
 }
 
+/// Keeps a Ruby object, and everything reachable from it, alive until the
+/// guard is dropped. The GC can't see `VALUE`s that only live in Rust data
+/// structures, so anything we hold on to across calls back in to Ruby needs
+/// one of these.
+pub struct GcGuard(Box<VALUE>);
+
+impl GcGuard {
+    pub fn new(v: VALUE) -> Self {
+        // The GC holds on to the address we register, so it must not move
+        // for as long as the guard is alive, hence the box.
+        let mut boxed = Box::new(v);
+        unsafe { rb_gc_register_address(&mut *boxed) };
+        GcGuard(boxed)
+    }
+
+    pub fn value(&self) -> VALUE {
+        *self.0
+    }
+}
+
+impl Drop for GcGuard {
+    fn drop(&mut self) {
+        unsafe { rb_gc_unregister_address(&mut *self.0) };
+    }
+}
+
 pub fn raise(s: &str) {
     let cstr = CString::new(s).expect("input string has a null byte");
     unsafe {
@@ -19,8 +19,7 @@ pub unsafe fn load_rubyfmt() -> Result<(), ()> {
     Ok(())
 }
 
-#[derive(Debug, Copy, Clone)]
-pub struct Parser(VALUE);
+pub struct Parser(GcGuard);
 
 #[derive(Debug, Clone)]
 pub enum ParseError {
@@ -38,24 +37,25 @@ impl Parser {
             let buffer_string = rb_utf8_str_new(buf.as_ptr() as _, buf.len() as libc::c_long);
             let parser_class = rb_const_get_at(rb_cObject, intern!("Parser"));
             let parser_instance = rb_funcall(parser_class, intern!("new"), 1, buffer_string);
-            Parser(parser_instance)
+            Parser(GcGuard::new(parser_instance))
         }
     }
 
-    pub fn parse(self) -> Result<(RipperTree, FileComments, Option<&'static str>), ParseError> {
+    pub fn parse(self) -> Result<(RipperTree, FileComments, Option<String>), ParseError> {
         let mut state = 0;
         let maybe_ret_tuple =
-            unsafe { rb_protect(Parser::real_run_parser as _, self.0 as _, &mut state) };
+            unsafe { rb_protect(Parser::real_run_parser as _, self.0.value(), &mut state) };
         if state == 0 {
             if maybe_ret_tuple != Qnil {
-                let ret_tuple = unsafe { ruby_array_to_slice(maybe_ret_tuple) };
+                let ret_guard = GcGuard::new(maybe_ret_tuple);
+                let ret_tuple = unsafe { ruby_array_to_slice(ret_guard.value()) };
                 if let [tree, comments, lines, last_lineno, end_contents] = ret_tuple {
                     let fc = FileComments::from_ruby_hash(*comments, *lines, *last_lineno);
                     let end_contents = unsafe {
                         if rubyfmt_rb_nil_p(*end_contents) != 0 {
                             None
                         } else {
-                            Some(ruby_string_to_str(*end_contents))
+                            Some(ruby_string_to_str(*end_contents).to_owned())
                         }
                     };
                     Ok((RipperTree::new(*tree), fc, end_contents))
@@ -75,15 +75,16 @@ impl Parser {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct RipperTree(VALUE);
+/// The tree returned by ripper. It stays reachable by the Ruby GC for as
+/// long as this is alive, so it must outlive any deserialization of it.
+pub struct RipperTree(GcGuard);
 
 impl RipperTree {
     pub fn new(v: VALUE) -> Self {
-        RipperTree(v)
+        RipperTree(GcGuard::new(v))
     }
 
-    pub fn into_value(self) -> VALUE {
-        self.0
+    pub fn value(&self) -> VALUE {
+        self.0.value()
     }
 }
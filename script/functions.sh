@@ -55,7 +55,7 @@ test_fixtures_folder() {
     done
 
     ## Recurse over ruby version dirs
-    find "$current_dir" -type d -mindepth 1 -maxdepth 1 -name '*2.*' | while read -r dir
+    find "$current_dir" -type d -mindepth 1 -maxdepth 1 -name '*[0-9].[0-9]*' | while read -r dir
     do
         RUBY_VERSION=$(ruby -v | grep -o "[0-9].[0-9]" | head -n 1)
         base="$(basename "$dir")"
@@ -66,3 +66,26 @@ test_fixtures_folder() {
         fi
     done
 }
+
+# Like test_fixtures_folder, but each `<name>_actual.rb`/`<name>_expected.rb`
+# pair is formatted with `<name>.rubyfmt.json` as the config
+test_configured_fixtures_folder() {
+    current_dir="$1"
+
+    # Fallback to * (all tests)
+    fixture_name=${FIXTURE_NAME:-*}
+
+    find "$current_dir" -name "${fixture_name}_expected.rb" -maxdepth 1 | while read -r expected_file; do
+      actual_file="${expected_file//expected/actual}"
+      config_dir=$(mktemp -d)
+      cp "${expected_file%_expected.rb}.rubyfmt.json" "$config_dir/.rubyfmt.json"
+
+      ## Test if the formatting works as expected
+      (cd "$config_dir" && f_rubyfmt) < "$actual_file" > /tmp/out.rb
+      diff_files o /tmp/out.rb "$expected_file"
+
+      ## Test if the formatting is idempotent
+      (cd "$config_dir" && f_rubyfmt) < "$expected_file" > /tmp/out.rb
+      diff_files i /tmp/out.rb "$expected_file"
+    done
+}
@@ -33,9 +33,11 @@ esac
 export RUBYFMT_USE_RELEASE=1
 uname -a
 ./script/tests/test_string_literals.sh
+./script/tests/test_numeric_literals.sh
 ./script/tests/test_array_literals.sh
 ./script/tests/test_methods.sh
 ./script/tests/test_cli_interface.sh
+./script/tests/test_gc_soak.sh
 ./script/tests/test_c_main.sh
 ./script/tests/test_error_handling.sh
 ./script/tests/test_fixtures.sh
@@ -654,6 +654,398 @@ DIFF
     )
 }
 
+test_respects_paren_less_methods_config() {
+    (
+    cd "$(mktemp -d)"
+    mkdir -p app spec
+
+    cat > .rubyfmt.json <<- JSON
+{
+  "paren_less_methods": [
+    {"methods": ["validates", "before_action"], "style": "omit"},
+    {"methods": ["context"], "style": "omit", "files": ["spec/**"]},
+    {"methods": ["scope"], "style": "preserve"}
+  ]
+}
+JSON
+
+    cat > app/user.rb <<- RUBY
+class User
+  validates(:name, presence: true)
+  validates({ name: true })
+
+  def setup
+    before_action(:authenticate)
+    x = before_action(:authenticate)
+  end
+end
+
+scope :active
+scope(:inactive)
+
+context "elsewhere" do
+end
+RUBY
+
+    cat > spec/user_spec.rb <<- RUBY
+describe "User" do
+  context("with a name") do
+  end
+end
+RUBY
+
+    f_rubyfmt -i -- .
+
+    cat > app/user_expected.rb <<- RUBY
+class User
+  validates :name, presence: true
+  validates({name: true})
+
+  def setup
+    before_action :authenticate
+    x = before_action(:authenticate)
+  end
+end
+
+scope :active
+scope(:inactive)
+
+context("elsewhere") do
+end
+RUBY
+
+    cat > spec/user_spec_expected.rb <<- RUBY
+describe "User" do
+  context "with a name" do
+  end
+end
+RUBY
+
+    diff_files o app/user_expected.rb app/user.rb
+    diff_files o spec/user_spec_expected.rb spec/user_spec.rb
+    )
+}
+
+test_finds_config_in_parent_directories() {
+    (
+    cd "$(mktemp -d)"
+    mkdir -p project/spec/models project/lib
+
+    cat > project/.rubyfmt.json <<- JSON
+{
+  "quote_style": "single",
+  "paren_less_methods": [
+    {"methods": ["context"], "style": "omit", "files": ["spec/**"]}
+  ]
+}
+JSON
+
+    echo 'context("a") do; end' > project/spec/models/user_spec.rb
+    echo 'context("a") do; end' > project/lib/user.rb
+
+    # Run from outside the project, `files` globs are relative to the config
+    f_rubyfmt -i -- project
+
+    printf "context 'a' do\nend\n" > expected_spec.rb
+    printf "context('a') do\nend\n" > expected_lib.rb
+
+    diff_files o expected_spec.rb project/spec/models/user_spec.rb
+    diff_files o expected_lib.rb project/lib/user.rb
+    )
+}
+
+test_rejects_unknown_config_fields() {
+    (
+    cd "$(mktemp -d)"
+
+    echo '{"quote_styel": "single"}' > .rubyfmt.json
+    echo "a 1,2,3" > input.rb
+
+    set +e
+    f_rubyfmt -- input.rb > out.rb 2> err.txt
+    exit_code=$?
+    set -e
+
+    if [ "$exit_code" -ne 3 ] || ! grep -q "unknown field \`quote_styel\`" err.txt; then
+        echo "expected the misspelled field to be rejected"
+        cat err.txt
+        exit 1
+    fi
+    )
+}
+
+test_stdin_filepath_finds_config() {
+    (
+    cd "$(mktemp -d)"
+    mkdir -p project/spec
+
+    cat > project/.rubyfmt.json <<- JSON
+{
+  "quote_style": "single",
+  "paren_less_methods": [
+    {"methods": ["context"], "style": "omit", "files": ["spec/**"]}
+  ]
+}
+JSON
+
+    echo 'context("a") do; end' | f_rubyfmt --stdin-filepath project/spec/user_spec.rb > out.rb
+    printf "context 'a' do\nend\n" > expected.rb
+    diff_files o out.rb expected.rb
+
+    # Without it STDIN is a file in the working directory, which has no config
+    echo 'context("a") do; end' | f_rubyfmt > out.rb
+    printf "context(\"a\") do\nend\n" > expected.rb
+    diff_files o out.rb expected.rb
+    )
+}
+
+test_respects_quote_style_config() {
+    (
+    cd "$(mktemp -d)"
+
+    cat > input.rb <<- 'RUBY'
+a = "plain"
+b = 'it\'s'
+c = "#{a} interpolated"
+d = "tab\t"
+e = :"sym bol"
+f = %q(percent)
+g = %Q(it's)
+RUBY
+
+    echo '{"quote_style": "single"}' > .rubyfmt.json
+    f_rubyfmt input.rb > out.rb
+
+    cat > expected.rb <<- 'RUBY'
+a = 'plain'
+b = "it's"
+c = "#{a} interpolated"
+d = "tab\t"
+e = :'sym bol'
+f = 'percent'
+g = "it's"
+RUBY
+
+    diff_files o out.rb expected.rb
+
+    echo '{"quote_style": "preserve"}' > .rubyfmt.json
+    f_rubyfmt input.rb > out.rb
+
+    cat > expected.rb <<- 'RUBY'
+a = "plain"
+b = 'it\'s'
+c = "#{a} interpolated"
+d = "tab\t"
+e = :"sym bol"
+RUBY
+
+    diff_files o out.rb expected.rb
+    )
+}
+
+test_respects_hash_key_style_config() {
+    (
+    cd "$(mktemp -d)"
+
+    cat > input.rb <<- 'RUBY'
+a = { :foo => 1, :"foo-bar" => 2, :if => 3, :Const => 4, :setter= => 5 }
+b = { :foo => 1, "string" => 2 }
+c = { :foo => 1, bar: 2, **opts }
+d = { :+ => 1, :[] => 2, :@ivar => 3, :$" => 4 }
+e = { :+ => 1, :[] => 2, :@ivar => 3 }
+call(:a => 1, :b? => 2)
+RUBY
+
+    echo '{"hash_key_style": "prefer_labels"}' > .rubyfmt.json
+    f_rubyfmt input.rb > out.rb
+
+    cat > expected.rb <<- 'RUBY'
+a = {foo: 1, "foo-bar": 2, if: 3, Const: 4, "setter=": 5}
+b = {:foo => 1, "string" => 2}
+c = {foo: 1, bar: 2, **opts}
+d = {:+ => 1, :[] => 2, :@ivar => 3, :$" => 4}
+e = {"+": 1, "[]": 2, "@ivar": 3}
+call(a: 1, b?: 2)
+RUBY
+
+    diff_files o out.rb expected.rb
+    )
+}
+
+test_respects_trailing_commas_config() {
+    (
+    cd "$(mktemp -d)"
+
+    cat > input.rb <<- 'RUBY'
+a = [
+  1,
+  2
+]
+b = {
+  foo: 1,
+  bar: 2
+}
+call(
+  a,
+  *rest,
+  key: 1
+)
+call(
+  a,
+  &blk
+)
+c = [1, 2]
+RUBY
+
+    echo '{"trailing_commas": "always"}' > .rubyfmt.json
+    f_rubyfmt input.rb > out.rb
+
+    cat > expected.rb <<- 'RUBY'
+a = [
+  1,
+  2,
+]
+b = {
+  foo: 1,
+  bar: 2,
+}
+call(
+  a,
+  *rest,
+  key: 1,
+)
+call(
+  a,
+  &blk
+)
+c = [1, 2]
+RUBY
+
+    diff_files o out.rb expected.rb
+
+    echo '{"trailing_commas": "only_literals"}' > .rubyfmt.json
+    f_rubyfmt input.rb > out.rb
+
+    cat > expected.rb <<- 'RUBY'
+a = [
+  1,
+  2,
+]
+b = {
+  foo: 1,
+  bar: 2,
+}
+call(
+  a,
+  *rest,
+  key: 1
+)
+call(
+  a,
+  &blk
+)
+c = [1, 2]
+RUBY
+
+    diff_files o out.rb expected.rb
+    )
+}
+
+test_respects_numeric_literals_config() {
+    (
+    cd "$(mktemp -d)"
+
+    cat > input.rb <<- 'RUBY'
+a = 1000
+b = 1000000
+c = 1_0000_0000
+d = 0XFF
+e = 0B1010
+f = 1.5E+10
+g = 1234567r
+h = 0755
+RUBY
+
+    f_rubyfmt input.rb > out.rb
+    diff_files o out.rb input.rb
+
+    echo '{"numeric_literals": {}}' > .rubyfmt.json
+    f_rubyfmt input.rb > out.rb
+
+    cat > expected.rb <<- 'RUBY'
+a = 1000
+b = 1_000_000
+c = 1_0000_0000
+d = 0xff
+e = 0b1010
+f = 1.5e+10
+g = 1_234_567r
+h = 0755
+RUBY
+
+    diff_files o out.rb expected.rb
+
+    echo '{"numeric_literals": {"group_size": 4, "min_digits": 4}}' > .rubyfmt.json
+    f_rubyfmt input.rb > out.rb
+
+    cat > expected.rb <<- 'RUBY'
+a = 1000
+b = 100_0000
+c = 1_0000_0000
+d = 0xff
+e = 0b1010
+f = 1.5e+10
+g = 123_4567r
+h = 0755
+RUBY
+
+    diff_files o out.rb expected.rb
+    )
+}
+
+test_respects_sort_requires_config() {
+    (
+    cd "$(mktemp -d)"
+
+    cat > input.rb <<- 'RUBY'
+require "json"
+require "set" # for Set#to_json
+require "bundler/setup"
+
+require_relative "lib/zebra"
+require_relative "lib/apple"
+
+require "b_loaded_first"
+
+require "a_loaded_second"
+# not about a require
+foo
+RUBY
+
+    echo '{"sort_requires": true}' > .rubyfmt.json
+    f_rubyfmt input.rb > out.rb
+
+    cat > expected.rb <<- 'RUBY'
+require "bundler/setup"
+require "json"
+# for Set#to_json
+require "set"
+
+require_relative "lib/apple"
+require_relative "lib/zebra"
+
+require "b_loaded_first"
+
+require "a_loaded_second"
+
+# not about a require
+foo
+RUBY
+
+    diff_files o out.rb expected.rb
+    )
+}
+
 test_formats_non_rb_files() {
     (
     cd "$(mktemp -d)"
@@ -671,6 +1063,272 @@ DIFF
     )
 }
 
+test_formats_rbi_files_in_directories() {
+    (
+    cd "$(mktemp -d)"
+    mkdir rbi/
+    echo "def foo(a,b); end" > rbi/foo.rbi
+
+    f_rubyfmt -i -- rbi/
+
+    cat > foo_expected.rbi <<- RUBY
+def foo(a, b)
+end
+RUBY
+
+    diff_files o foo_expected.rbi rbi/foo.rbi
+    )
+}
+
+test_formats_markdown_code_blocks() {
+    (
+    cd "$(mktemp -d)"
+    mkdir docs/
+
+    cat > docs/guide.md <<- 'MD'
+# Guide
+
+Call it like a 1,2,3:
+
+```ruby
+a 1,2,3
+```
+
+```rb
+broken(
+```
+
+```bash
+a 1,2,3
+```
+
+Indented code isn't a fence:
+
+    ```ruby
+    a 1,2,3
+    ```
+
+1. But code in a list item is:
+
+   ```ruby
+   a 1,2,3
+   ```
+MD
+
+    echo "a 1,2,3" > docs/example.rb
+
+    f_rubyfmt -i --markdown -- docs/ 2> errors.log
+
+    cat > guide_expected.md <<- 'MD'
+# Guide
+
+Call it like a 1,2,3:
+
+```ruby
+a(1, 2, 3)
+```
+
+```rb
+broken(
+```
+
+```bash
+a 1,2,3
+```
+
+Indented code isn't a fence:
+
+    ```ruby
+    a 1,2,3
+    ```
+
+1. But code in a list item is:
+
+   ```ruby
+   a(1, 2, 3)
+   ```
+MD
+
+    echo "a(1, 2, 3)" > example_expected.rb
+
+    diff_files o guide_expected.md docs/guide.md
+    diff_files o example_expected.rb docs/example.rb
+    grep -q "docs/guide.md:10" errors.log
+    )
+}
+
+test_markdown_conflicts_with_erb() {
+    (
+    cd "$(mktemp -d)"
+
+    set +e
+    echo "a 1,2,3" | f_rubyfmt --markdown --erb > out.txt 2> err.txt
+    exit_code=$?
+    set -e
+
+    if [ "$exit_code" -eq 0 ] || ! grep -q "cannot be used with" err.txt; then
+        echo "expected --markdown and --erb to be rejected together"
+        cat err.txt
+        exit 1
+    fi
+    )
+}
+
+test_check_flag_markdown() {
+    (
+    cd "$(mktemp -d)"
+
+    cat > README.md <<- 'MD'
+```ruby
+a 1,2,3
+```
+MD
+
+    set +e
+    f_rubyfmt --check --markdown -- README.md > fmt.diff
+    set -e
+
+    cat > expected.diff <<- DIFF
+--- README.md
++++ README.md
+@@ -1,3 +1,3 @@
+ \`\`\`ruby
+-a 1,2,3
++a(1, 2, 3)
+ \`\`\`
+DIFF
+
+    cat expected.diff
+    cat fmt.diff
+
+    diff_files o expected.diff fmt.diff
+    )
+}
+
+test_markdown_skips_magic_comments() {
+    (
+    cd "$(mktemp -d)"
+    echo '{"magic_comments": {"normalize": true, "add_frozen_string_literal": true}}' > .rubyfmt.json
+
+    cat > README.md <<- 'MD'
+```ruby
+a 1,2,3
+```
+MD
+
+    f_rubyfmt -i --markdown -- README.md
+
+    cat > expected.md <<- 'MD'
+```ruby
+a(1, 2, 3)
+```
+MD
+
+    diff_files o expected.md README.md
+    )
+}
+
+test_formats_erb_templates() {
+    (
+    cd "$(mktemp -d)"
+    mkdir views/
+
+    cat > views/index.html.erb <<- 'ERB'
+<ul>
+  <% items.each do |item| %>
+    <li><%=link_to item.name,item %></li>
+      <% end %>
+</ul>
+<% if admin? %>
+  <p><%# a comment %></p>
+    <% else %>
+  <p><%= foo( %></p>
+    <% end %>
+ERB
+
+    f_rubyfmt -i --erb -- views/
+
+    cat > index_expected.html.erb <<- 'ERB'
+<ul>
+  <% items.each do |item| %>
+    <li><%= link_to(item.name, item) %></li>
+  <% end %>
+</ul>
+<% if admin? %>
+  <p><%# a comment %></p>
+<% else %>
+  <p><%= foo( %></p>
+<% end %>
+ERB
+
+    diff_files o index_expected.html.erb views/index.html.erb
+    )
+}
+
+test_daemon() {
+    (
+    cd "$(mktemp -d)"
+    export RUBYFMT_DAEMON_SOCKET="$PWD/rubyfmt.sock"
+
+    f_rubyfmt daemon 2> daemon.log &
+    daemon_pid=$!
+    trap 'kill $daemon_pid' EXIT
+
+    for _ in $(seq 1 50); do
+        [ -S "$RUBYFMT_DAEMON_SOCKET" ] && break
+        sleep 0.1
+    done
+
+    # The CLI picks up the running daemon
+    echo "a 1,2,3" > input.rb
+    echo "a(1, 2, 3)" > expected.rb
+
+    f_rubyfmt -- input.rb > out.rb
+    diff_files o out.rb expected.rb
+
+    echo "a 1,2,3" | f_rubyfmt > out.rb
+    diff_files o out.rb expected.rb
+
+    # And so can anything else that speaks the protocol
+    ruby -rsocket -rjson -e '
+      sock = UNIXSocket.new(ENV.fetch("RUBYFMT_DAEMON_SOCKET"))
+      [{source: "a 4,5,6\n"}, {source: "a(\n"}].each do |request|
+        body = JSON.generate(request)
+        sock.write([body.bytesize].pack("N") + body)
+        len = sock.read(4).unpack1("N")
+        puts sock.read(len)
+      end
+    ' > out.json
+
+    cat > expected.json <<- DIFF
+{"output":"a(4, 5, 6)\n"}
+{"error":{"kind":"syntax_error"}}
+DIFF
+
+    diff_files o out.json expected.json
+
+    # Bad JSON is answered and the connection stays usable, but a length that's
+    # too large leaves the body unread, so the daemon hangs up after answering
+    ruby -rsocket -rjson -e '
+      sock = UNIXSocket.new(ENV.fetch("RUBYFMT_DAEMON_SOCKET"))
+      read_response = -> { puts JSON.parse(sock.read(sock.read(4).unpack1("N"))).dig("error", "kind") }
+      sock.write([5].pack("N") + "nope!")
+      read_response.call
+      sock.write([0xFFFFFFFF].pack("N") + "{}")
+      read_response.call
+      p sock.read(4)
+    ' > out.txt
+
+    cat > expected.txt <<- DIFF
+bad_request
+bad_request
+nil
+DIFF
+
+    diff_files o out.txt expected.txt
+    )
+}
+
 test_simple_stdout
 test_i_flag
 test_i_flag_no_changes
@@ -708,5 +1366,26 @@ test_respects_gitignore
 test_includes_gitignore
 
 test_respects_rubyfmt_ignore_file
+test_respects_paren_less_methods_config
+test_finds_config_in_parent_directories
+test_stdin_filepath_finds_config
+test_rejects_unknown_config_fields
+test_respects_quote_style_config
+test_respects_hash_key_style_config
+
+test_respects_trailing_commas_config
+
+test_respects_numeric_literals_config
+
+test_respects_sort_requires_config
 
 test_formats_non_rb_files
+test_formats_rbi_files_in_directories
+
+test_formats_markdown_code_blocks
+test_markdown_conflicts_with_erb
+test_check_flag_markdown
+test_markdown_skips_magic_comments
+test_formats_erb_templates
+
+test_daemon
@@ -7,4 +7,5 @@ source "./script/functions.sh"
 make
 
 test_fixtures_folder "fixtures/small"
+test_configured_fixtures_folder "fixtures/configured"
 test_fixtures_folder "fixtures/large"
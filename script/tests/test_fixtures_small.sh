@@ -6,3 +6,4 @@ source "./script/functions.sh"
 make
 
 test_fixtures_folder "fixtures/small"
+test_configured_fixtures_folder "fixtures/configured"
@@ -0,0 +1,57 @@
+#!/bin/bash
+set -euxo pipefail
+
+source "./script/functions.sh"
+
+# Formats the fixture corpus over and over through a single daemon and makes
+# sure its memory use levels off instead of growing with every file.
+test_daemon_memory_is_flat() {
+    (
+    tmp_dir="$(mktemp -d)"
+    export RUBYFMT_DAEMON_SOCKET="$tmp_dir/rubyfmt.sock"
+
+    f_rubyfmt daemon 2> "$tmp_dir/daemon.log" &
+    daemon_pid=$!
+    trap 'kill $daemon_pid' EXIT
+
+    for _ in $(seq 1 50); do
+        [ -S "$RUBYFMT_DAEMON_SOCKET" ] && break
+        sleep 0.1
+    done
+
+    format_corpus() {
+        ruby -rsocket -rjson -e '
+          sock = UNIXSocket.new(ENV.fetch("RUBYFMT_DAEMON_SOCKET"))
+          files = Dir.glob("fixtures/{small,large}/**/*_actual.rb").map { |f| File.read(f) }
+          Integer(ARGV[0]).times do
+            files.each do |source|
+              body = JSON.generate(source: source)
+              sock.write([body.bytesize].pack("N") + body)
+              sock.read(sock.read(4).unpack1("N"))
+            end
+          end
+        ' "$1"
+    }
+
+    rss() {
+        ps -o rss= -p "$daemon_pid" | tr -d ' '
+    }
+
+    # Let the heap settle before taking a baseline
+    format_corpus 3
+    baseline=$(rss)
+
+    format_corpus 20
+    after=$(rss)
+
+    echo "rss after warmup: ${baseline}KB, after soak: ${after}KB"
+
+    # Some noise is fine, growing with the number of files formatted is not
+    if [ "$after" -gt $((baseline * 5 / 4)) ]; then
+        echo "rubyfmt daemon memory grew from ${baseline}KB to ${after}KB"
+        exit 1
+    fi
+    )
+}
+
+test_daemon_memory_is_flat
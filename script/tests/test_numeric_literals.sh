@@ -0,0 +1,19 @@
+#!/bin/bash
+set -ex
+
+source ./script/functions.sh
+
+NUMERIC_LITERALS_EXPECTED=$(ruby ci/numeric_literals_stress_test.rb | f_md5)
+
+for NUMERIC_LITERALS in '{}' '{"group_size": 4, "min_digits": 4}' '{"group_size": 1, "min_digits": 1}'
+do
+    CONFIG_DIR=$(mktemp -d)
+    echo "{\"numeric_literals\": $NUMERIC_LITERALS}" > "$CONFIG_DIR/.rubyfmt.json"
+
+    NUMERIC_LITERALS_ACTUAL=$(cd "$CONFIG_DIR" && f_rubyfmt < "$REPO_BASE/ci/numeric_literals_stress_test.rb" | ruby | f_md5)
+    if [[ "$NUMERIC_LITERALS_EXPECTED" != "$NUMERIC_LITERALS_ACTUAL" ]]
+    then
+        echo "numeric literals are broken with numeric_literals $NUMERIC_LITERALS"
+        exit 1
+    fi
+done
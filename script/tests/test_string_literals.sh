@@ -4,9 +4,16 @@ set -ex
 source ./script/functions.sh
 
 STRING_LITERALS_EXPECTED=$(ruby ci/string_literals_stress_test.rb | f_md5)
-STRING_LITERALS_ACTUAL=$(f_rubyfmt < ci/string_literals_stress_test.rb | ruby | f_md5)
-if [[ "$STRING_LITERALS_EXPECTED" != "$STRING_LITERALS_ACTUAL" ]]
-then
-    echo "string literals are broken"
-    exit 1
-fi
+
+for QUOTE_STYLE in double single preserve
+do
+    CONFIG_DIR=$(mktemp -d)
+    echo "{\"quote_style\": \"$QUOTE_STYLE\"}" > "$CONFIG_DIR/.rubyfmt.json"
+
+    STRING_LITERALS_ACTUAL=$(cd "$CONFIG_DIR" && f_rubyfmt < "$REPO_BASE/ci/string_literals_stress_test.rb" | ruby | f_md5)
+    if [[ "$STRING_LITERALS_EXPECTED" != "$STRING_LITERALS_ACTUAL" ]]
+    then
+        echo "string literals are broken with quote_style $QUOTE_STYLE"
+        exit 1
+    fi
+done
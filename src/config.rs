@@ -0,0 +1,233 @@
+//! Project level settings, read from the `.rubyfmt.json` closest to each
+//! formatted file, searching its directory and then each parent. For example:
+//!
+//! ```json
+//! {
+//!   "quote_style": "single",
+//!   "hash_key_style": "prefer_labels",
+//!   "trailing_commas": "always",
+//!   "boolean_operators": "leading",
+//!   "call_chains": {"dot_position": "trailing", "fill_lines": true},
+//!   "block_delimiters": "line_count_based",
+//!   "split_long_strings": true,
+//!   "max_ternary_depth": 1,
+//!   "numeric_literals": {"group_size": 3, "min_digits": 5},
+//!   "sort_requires": true,
+//!   "blank_lines": {"max_consecutive": 1, "trim_body_edges": true},
+//!   "access_modifiers": {"indent_sections": true, "style": "inline"},
+//!   "magic_comments": {"normalize": true, "add_frozen_string_literal": true},
+//!   "reflow_comments": true,
+//!   "format_doc_examples": true,
+//!   "ruby_heredocs": ["RUBY"],
+//!   "paren_less_methods": [
+//!     {"methods": ["validates", "has_many", "belongs_to"], "style": "omit"},
+//!     {"methods": ["describe", "context", "it"], "style": "omit", "files": ["spec/**"]}
+//!   ]
+//! }
+//! ```
+//!
+//! Rules without `files` apply everywhere, otherwise the globs are relative
+//! to the directory holding the `.rubyfmt.json`. When more than one rule
+//! mentions a method for the same file, the last one wins.
+
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::read_to_string;
+use std::io::{self, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+const CONFIG_FILE_NAME: &str = ".rubyfmt.json";
+
+/// Every `.rubyfmt.json` read so far, so files in the same project only
+/// parse their config once
+#[derive(Debug, Default)]
+pub struct Configs {
+    loaded: RefCell<HashMap<PathBuf, Rc<Config>>>,
+}
+
+impl Configs {
+    /// The config that applies to `file_path`, which doesn't have to exist,
+    /// e.g. the path given to `--stdin-filepath`. Errors come with the path
+    /// of the config that failed to load.
+    pub fn for_file(&self, file_path: &Path) -> Result<Rc<Config>, (io::Error, PathBuf)> {
+        let config_path = match find_config_file(file_path) {
+            Some(config_path) => config_path,
+            None => return Ok(Rc::new(Config::default())),
+        };
+
+        if let Some(config) = self.loaded.borrow().get(&config_path) {
+            return Ok(config.clone());
+        }
+
+        let config = Rc::new(Config::load(&config_path).map_err(|e| (e, config_path.clone()))?);
+        self.loaded.borrow_mut().insert(config_path, config.clone());
+        Ok(config)
+    }
+}
+
+/// Searches the directory `file_path` is in and then each of its parents
+fn find_config_file(file_path: &Path) -> Option<PathBuf> {
+    absolute_path(file_path)
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    // The directory holding the `.rubyfmt.json`, `files` globs are relative to it
+    root: PathBuf,
+    // Everything but the paren-less methods, which depend on the file
+    base: rubyfmt::FormatOptions,
+    paren_less_methods: Vec<ParenLessMethods>,
+}
+
+#[derive(Debug)]
+struct ParenLessMethods {
+    methods: Vec<String>,
+    style: ParenStyle,
+    // Empty means every file
+    files: Vec<Pattern>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ParenStyle {
+    /// Keep the parens exactly as the author wrote them
+    Preserve,
+    /// Never use parens when the call is a statement of its own
+    Omit,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawConfig {
+    paren_less_methods: Vec<RawParenLessMethods>,
+    #[serde(flatten)]
+    base: rubyfmt::FormatOptions,
+    // `deny_unknown_fields` doesn't work together with `flatten`, so whatever
+    // `base` didn't take ends up here instead
+    #[serde(flatten)]
+    unknown: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawParenLessMethods {
+    methods: Vec<String>,
+    style: ParenStyle,
+    #[serde(default)]
+    files: Vec<String>,
+}
+
+impl Config {
+    fn load(path: &Path) -> io::Result<Config> {
+        let raw: RawConfig = serde_json::from_str(&read_to_string(path)?)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        if let Some(field) = raw.unknown.keys().next() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown field `{}`", field),
+            ));
+        }
+
+        let paren_less_methods = raw
+            .paren_less_methods
+            .into_iter()
+            .map(|rule| {
+                let files = rule
+                    .files
+                    .iter()
+                    .map(|f| Pattern::new(f))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+                Ok(ParenLessMethods {
+                    methods: rule.methods,
+                    style: rule.style,
+                    files,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Config {
+            root: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            base: raw.base,
+            paren_less_methods,
+        })
+    }
+
+    pub fn format_options_for(&self, file_path: &Path) -> rubyfmt::FormatOptions {
+        let mut options = self.base.clone();
+        let file_path = relative_to_root(&self.root, file_path);
+        let match_options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+
+        for rule in &self.paren_less_methods {
+            let applies = rule.files.is_empty()
+                || rule
+                    .files
+                    .iter()
+                    .any(|f| f.matches_path_with(&file_path, match_options));
+            if !applies {
+                continue;
+            }
+
+            for method in &rule.methods {
+                let (add_to, remove_from) = match rule.style {
+                    ParenStyle::Preserve => (
+                        &mut options.preserve_parens_methods,
+                        &mut options.omit_parens_methods,
+                    ),
+                    ParenStyle::Omit => (
+                        &mut options.omit_parens_methods,
+                        &mut options.preserve_parens_methods,
+                    ),
+                };
+                remove_from.remove(method);
+                add_to.insert(method.clone());
+            }
+        }
+
+        options
+    }
+}
+
+/// Patterns are written relative to the config's directory, so
+/// `./spec/foo_spec.rb` and `/path/to/project/spec/foo_spec.rb` both need to
+/// look like `spec/foo_spec.rb` before matching.
+fn relative_to_root(root: &Path, file_path: &Path) -> PathBuf {
+    let file_path = absolute_path(file_path);
+    match file_path.strip_prefix(root) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => file_path,
+    }
+}
+
+/// Joins relative paths onto the working directory and drops any `.` and
+/// `..` components, without touching the filesystem since the path might
+/// not exist
+fn absolute_path(file_path: &Path) -> PathBuf {
+    let joined = match std::env::current_dir() {
+        Ok(cwd) => cwd.join(file_path),
+        Err(_) => file_path.to_path_buf(),
+    };
+
+    let mut absolute = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            c => absolute.push(c),
+        }
+    }
+    absolute
+}
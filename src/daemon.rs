@@ -0,0 +1,355 @@
+//! A long running rubyfmt process that formats files sent to it over a Unix
+//! domain socket. Booting the ruby VM and loading ripper costs more than
+//! formatting most files, so editors that format on every save can talk to a
+//! warm daemon instead of paying that cost each time.
+//!
+//! Every message, in both directions, is a 4 byte big-endian length followed
+//! by that many bytes of JSON. A client may send any number of requests over
+//! a single connection, each request gets exactly one response.
+//!
+//! Requests look like:
+//!
+//! ```json
+//! {"source": "a 1,2,3\n", "path": "lib/foo.rb", "options": {"header_opt_in": false}}
+//! ```
+//!
+//! `path` and `options` are optional. `options.format` takes the fields of
+//! `rubyfmt::FormatOptions`, e.g. `{"omit_parens_methods": ["validates"]}`.
+//!
+//! Responses carry either the formatted `output` or an `error`. If both are
+//! absent the file was skipped because of a header option and the source
+//! should be left as is.
+//!
+//! Connections are served one at a time, so a client that goes quiet for
+//! longer than `CONNECTION_TIMEOUT` is hung up on to let the others through.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, DirBuilder};
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Largest message either side is willing to read, this is much bigger than
+/// any ruby file we expect to see and stops us allocating garbage lengths.
+const MAX_MESSAGE_LENGTH: u32 = 256 * 1024 * 1024;
+
+/// Set this to override the default socket location, for both the daemon
+/// and the client.
+const SOCKET_ENV_VAR: &str = "RUBYFMT_DAEMON_SOCKET";
+
+/// How long the daemon waits on a client that's in the middle of a
+/// connection before dropping it
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a client waits for a response, this covers formatting as well
+/// as waiting for the daemon to finish with other clients
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub source: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub options: RequestOptions,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestOptions {
+    pub header_opt_in: bool,
+    pub header_opt_out: bool,
+    /// Already resolved for `path`, the daemon doesn't read `.rubyfmt.json`
+    pub format: rubyfmt::FormatOptions,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Response {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ResponseError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ResponseError {
+    SyntaxError,
+    RipperParseFailure { message: String },
+    IoError { message: String },
+    OtherRubyError { message: String },
+    BadRequest { message: String },
+    Panic { message: String },
+}
+
+impl From<rubyfmt::RichFormatError> for ResponseError {
+    fn from(e: rubyfmt::RichFormatError) -> Self {
+        use rubyfmt::RichFormatError::*;
+        match e {
+            SyntaxError => ResponseError::SyntaxError,
+            RipperParseFailure(e) => ResponseError::RipperParseFailure {
+                message: e.to_string(),
+            },
+            IOError(e) => ResponseError::IoError {
+                message: e.to_string(),
+            },
+            OtherRubyError(message) => ResponseError::OtherRubyError { message },
+        }
+    }
+}
+
+impl From<ResponseError> for rubyfmt::RichFormatError {
+    fn from(e: ResponseError) -> Self {
+        use rubyfmt::RichFormatError::*;
+        match e {
+            ResponseError::SyntaxError => SyntaxError,
+            ResponseError::RipperParseFailure { message } => {
+                RipperParseFailure(serde::de::Error::custom(message))
+            }
+            // `io::Error::other` needs a newer Rust than we support
+            #[allow(unknown_lints, clippy::io_other_error)]
+            ResponseError::IoError { message } => {
+                IOError(io::Error::new(ErrorKind::Other, message))
+            }
+            ResponseError::OtherRubyError { message } => OtherRubyError(message),
+            ResponseError::BadRequest { message } => {
+                OtherRubyError(format!("rubyfmt daemon rejected request: {}", message))
+            }
+            ResponseError::Panic { message } => {
+                OtherRubyError(format!("rubyfmt daemon panicked: {}", message))
+            }
+        }
+    }
+}
+
+pub fn default_socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_ENV_VAR) {
+        return PathBuf::from(path);
+    }
+
+    let uid = current_uid();
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join(format!("rubyfmt-{}.sock", uid)),
+        // The temp dir is shared with other users, so the socket goes in a
+        // directory only we can get into
+        None => std::env::temp_dir()
+            .join(format!("rubyfmt-{}", uid))
+            .join("rubyfmt.sock"),
+    }
+}
+
+fn current_uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+// Another user who could put a socket here first would get to answer every
+// request, so both the socket and the directory it's in have to be ours
+// (or root's, for directories like /tmp)
+fn is_trusted_socket(socket_path: &Path) -> bool {
+    let uid = current_uid();
+    let socket_is_ours = fs::symlink_metadata(socket_path)
+        .map(|m| m.uid() == uid)
+        .unwrap_or(false);
+    let parent = match socket_path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+        Some(parent) => parent,
+        None => return false,
+    };
+    let parent_is_trusted = fs::metadata(parent)
+        .map(|m| m.uid() == uid || m.uid() == 0)
+        .unwrap_or(false);
+    socket_is_ours && parent_is_trusted
+}
+
+/******************************************************/
+/* Framing                                            */
+/******************************************************/
+
+fn read_message<T: for<'de> Deserialize<'de>>(stream: &mut UnixStream) -> io::Result<Option<T>> {
+    let mut len_bytes = [0; 4];
+    match stream.read_exact(&mut len_bytes) {
+        Ok(()) => {}
+        // The other side hung up between messages, this is how
+        // connections are expected to end.
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let len = u32::from_be_bytes(len_bytes);
+    // The body is left unread, so the stream can't be read from after this
+    if len > MAX_MESSAGE_LENGTH {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("message of {} bytes is too large", len),
+        ));
+    }
+
+    let mut buf = vec![0; len as usize];
+    stream.read_exact(&mut buf)?;
+    serde_json::from_slice(&buf)
+        .map(Some)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+fn write_message<T: Serialize>(stream: &mut UnixStream, message: &T) -> io::Result<()> {
+    let buf = serde_json::to_vec(message).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    stream.write_all(&(buf.len() as u32).to_be_bytes())?;
+    stream.write_all(&buf)?;
+    stream.flush()
+}
+
+/******************************************************/
+/* Server                                             */
+/******************************************************/
+
+/// Runs the daemon until the process is killed. `format` is called for each
+/// request, connections are served one at a time since the ruby VM may
+/// only be used from a single thread.
+pub fn serve(
+    socket_path: &Path,
+    format: &dyn Fn(&Request) -> Result<Option<String>, rubyfmt::RichFormatError>,
+) -> io::Result<()> {
+    if let Some(parent) = socket_path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            DirBuilder::new().mode(0o700).create(parent)?;
+        }
+    }
+
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(io::Error::new(
+                ErrorKind::AddrInUse,
+                format!(
+                    "a rubyfmt daemon is already listening on {}",
+                    socket_path.display()
+                ),
+            ));
+        }
+        // Nobody is listening, this was left behind by a daemon that was killed
+        fs::remove_file(socket_path)?;
+    }
+
+    let listener = UnixListener::bind(socket_path)?;
+    eprintln!("rubyfmt daemon listening on {}", socket_path.display());
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(mut stream) => {
+                let res = stream
+                    .set_read_timeout(Some(CONNECTION_TIMEOUT))
+                    .and_then(|_| stream.set_write_timeout(Some(CONNECTION_TIMEOUT)))
+                    .and_then(|_| handle_connection(&mut stream, format));
+                if let Err(e) = res {
+                    eprintln!("rubyfmt daemon connection error: {}", e);
+                }
+            }
+            Err(e) => eprintln!("rubyfmt daemon failed to accept connection: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_connection(
+    stream: &mut UnixStream,
+    format: &dyn Fn(&Request) -> Result<Option<String>, rubyfmt::RichFormatError>,
+) -> io::Result<()> {
+    loop {
+        let response = match read_message::<Request>(stream) {
+            Ok(None) => return Ok(()),
+            Ok(Some(request)) => match panic::catch_unwind(AssertUnwindSafe(|| format(&request))) {
+                Ok(Ok(output)) => Response {
+                    output,
+                    error: None,
+                },
+                Ok(Err(e)) => Response {
+                    output: None,
+                    error: Some(e.into()),
+                },
+                // The panic hook has already logged it, the daemon keeps serving
+                Err(payload) => Response {
+                    output: None,
+                    error: Some(ResponseError::Panic {
+                        message: panic_message(payload.as_ref()),
+                    }),
+                },
+            },
+            // The whole message was read, so the next one can still be
+            Err(e) if e.kind() == ErrorKind::InvalidData => Response {
+                output: None,
+                error: Some(ResponseError::BadRequest {
+                    message: e.to_string(),
+                }),
+            },
+            // The message was too large to read, so there's no telling where
+            // the next one starts. The client gets told why before we hang up.
+            Err(e) if e.kind() == ErrorKind::InvalidInput => {
+                let response = Response {
+                    output: None,
+                    error: Some(ResponseError::BadRequest {
+                        message: e.to_string(),
+                    }),
+                };
+                return write_message(stream, &response);
+            }
+            Err(e) => return Err(e),
+        };
+
+        write_message(stream, &response)?;
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/******************************************************/
+/* Client                                             */
+/******************************************************/
+
+pub struct Client {
+    stream: UnixStream,
+}
+
+impl Client {
+    /// Returns `None` when no daemon we trust is listening on `socket_path`,
+    /// callers are expected to fall back to formatting in process.
+    pub fn connect(socket_path: &Path) -> Option<Client> {
+        if !is_trusted_socket(socket_path) {
+            return None;
+        }
+        let stream = UnixStream::connect(socket_path).ok()?;
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT)).ok()?;
+        stream.set_write_timeout(Some(RESPONSE_TIMEOUT)).ok()?;
+        Some(Client { stream })
+    }
+
+    /// The outer error means the daemon couldn't be talked to, and the
+    /// request should be formatted some other way
+    pub fn format(
+        &mut self,
+        request: &Request,
+    ) -> io::Result<Result<Option<String>, rubyfmt::RichFormatError>> {
+        write_message(&mut self.stream, request)?;
+        let response: Response = read_message(&mut self.stream)?.ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "rubyfmt daemon closed the connection",
+            )
+        })?;
+
+        Ok(match response.error {
+            Some(e) => Err(e.into()),
+            None => Ok(response.output),
+        })
+    }
+}
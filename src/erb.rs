@@ -0,0 +1,207 @@
+//! Finds the Ruby code in ERB templates, e.g.
+//!
+//! ```erb
+//! <% if user %>
+//!   <p><%= link_to user.name,user_path(user) %></p>
+//!     <% end %>
+//! ```
+//!
+//! A tag whose code parses on its own is formatted when the result still fits
+//! on one line. Tags like `<% if user %>` and `<% end %>` only make sense
+//! together, so they're kept as they are, but a line that starts with e.g.
+//! `<% end %>` gets the indentation of the line its `<% if %>` is on.
+//! Everything else in the template is kept byte for byte.
+
+use std::ffi::OsStr;
+use std::path::Path;
+
+pub fn is_erb_path(path: &Path) -> bool {
+    path.extension().and_then(OsStr::to_str) == Some("erb")
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Tag(Tag<'a>),
+}
+
+struct Tag<'a> {
+    // e.g. `<%=` or `<%-`
+    open: &'a str,
+    code: &'a str,
+    // `%>` or `-%>`
+    close: &'a str,
+    line: usize,
+}
+
+impl<'a> Tag<'a> {
+    fn source(&self) -> String {
+        format!("{}{}{}", self.open, self.code, self.close)
+    }
+
+    fn is_comment(&self) -> bool {
+        self.open == "<%#"
+    }
+}
+
+fn tokenize(template: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut search_from = 0;
+    while let Some(offset) = template[search_from..].find("<%") {
+        let tag_start = search_from + offset;
+        let rest = &template[tag_start..];
+        // `<%%` is a literal `<%`
+        if rest.starts_with("<%%") {
+            search_from = tag_start + 3;
+            continue;
+        }
+
+        let open_length = ["<%==", "<%=", "<%-", "<%#"]
+            .iter()
+            .find(|open| rest.starts_with(*open))
+            .map(|open| open.len())
+            .unwrap_or(2);
+        let close_offset = match rest[open_length..].find("%>") {
+            Some(close_offset) => open_length + close_offset,
+            None => break,
+        };
+        let (code, close) = match rest[open_length..close_offset].strip_suffix('-') {
+            Some(code) => (code, &rest[close_offset - 1..close_offset + 2]),
+            None => (
+                &rest[open_length..close_offset],
+                &rest[close_offset..close_offset + 2],
+            ),
+        };
+
+        if text_start < tag_start {
+            segments.push(Segment::Text(&template[text_start..tag_start]));
+        }
+        segments.push(Segment::Tag(Tag {
+            open: &rest[..open_length],
+            code,
+            close,
+            line: template[..tag_start].matches('\n').count() + 1,
+        }));
+        text_start = tag_start + close_offset + 2;
+        search_from = text_start;
+    }
+    if text_start < template.len() {
+        segments.push(Segment::Text(&template[text_start..]));
+    }
+    segments
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fragment {
+    // `if x`, `items.each do |item|`
+    Opening,
+    // `else`, `when 1`
+    Continuing,
+    // `end`
+    Closing,
+}
+
+const OPENING_KEYWORDS: &[&str] = &["if", "unless", "while", "until", "case", "for", "begin"];
+const CONTINUING_KEYWORDS: &[&str] = &["else", "elsif", "when", "in", "rescue", "ensure"];
+
+// Only called for code that doesn't parse on its own
+fn classify_fragment(code: &str) -> Option<Fragment> {
+    let first_word = code.split_whitespace().next()?;
+    if code == "end" || code == "}" {
+        Some(Fragment::Closing)
+    } else if CONTINUING_KEYWORDS.contains(&first_word) {
+        Some(Fragment::Continuing)
+    } else if OPENING_KEYWORDS.contains(&first_word) || opens_block(code) {
+        Some(Fragment::Opening)
+    } else {
+        None
+    }
+}
+
+// `items.each do`, `items.each do |item|`, or the same with `{`
+fn opens_block(code: &str) -> bool {
+    let code = match code.strip_suffix('|') {
+        Some(without_params) => match without_params.rfind('|') {
+            Some(params_start) => without_params[..params_start].trim_end(),
+            None => return false,
+        },
+        None => code,
+    };
+    code.ends_with('{')
+        || code
+            .strip_suffix("do")
+            .map(|before| before.ends_with(|c: char| c.is_whitespace() || c == ')'))
+            .unwrap_or(false)
+}
+
+fn current_line_indentation(output: &str) -> &str {
+    let line_start = output.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line = &output[line_start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+// Gives the tag that's about to be written the given indentation, as long as
+// nothing but whitespace comes before it on its line
+fn reindent_current_line(output: &mut String, indentation: &str) {
+    let line_start = output.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    if output[line_start..]
+        .trim_start_matches([' ', '\t'])
+        .is_empty()
+    {
+        output.truncate(line_start);
+        output.push_str(indentation);
+    }
+}
+
+/// Runs `format` over the code in each tag, along with the line number the
+/// tag is on. A tag is left as it is when `format` returns `None`.
+pub fn format_erb(template: &str, format: &mut dyn FnMut(usize, &str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut open_fragments: Vec<String> = Vec::new();
+    for segment in tokenize(template) {
+        let tag = match segment {
+            Segment::Text(text) => {
+                result.push_str(text);
+                continue;
+            }
+            Segment::Tag(tag) => tag,
+        };
+
+        let code = tag.code.trim();
+        if tag.is_comment() || code.is_empty() || tag.code.contains('\n') {
+            result.push_str(&tag.source());
+            continue;
+        }
+
+        match format(tag.line, code) {
+            Some(formatted) => {
+                let formatted = formatted.trim_end();
+                if formatted.is_empty() || formatted.contains('\n') {
+                    result.push_str(&tag.source());
+                } else {
+                    result.push_str(&format!("{} {} {}", tag.open, formatted, tag.close));
+                }
+            }
+            None => {
+                match classify_fragment(code) {
+                    Some(Fragment::Opening) => {
+                        open_fragments.push(current_line_indentation(&result).to_string())
+                    }
+                    Some(Fragment::Continuing) => {
+                        if let Some(indentation) = open_fragments.last() {
+                            reindent_current_line(&mut result, indentation);
+                        }
+                    }
+                    Some(Fragment::Closing) => {
+                        if let Some(indentation) = open_fragments.pop() {
+                            reindent_current_line(&mut result, &indentation);
+                        }
+                    }
+                    None => {}
+                }
+                result.push_str(&tag.source());
+            }
+        }
+    }
+    result
+}
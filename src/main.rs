@@ -4,16 +4,23 @@ use clap::Parser;
 use ignore::WalkBuilder;
 use regex::Regex;
 use similar::TextDiff;
+use std::cell::RefCell;
 use std::ffi::OsStr;
 use std::fs::{read_to_string, File, OpenOptions};
 use std::io::{self, BufRead, BufReader, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
 use std::sync::{Arc, Mutex};
 
 #[macro_use]
 extern crate lazy_static;
 
+mod config;
+#[cfg(unix)]
+mod daemon;
+mod erb;
+mod markdown;
+
 lazy_static! {
     static ref MAGIC_COMMENT_REGEX: Regex =
         Regex::new(r"(?m)^#\s*rubyfmt:\s*(?P<enabled>true|false)\s*$").unwrap();
@@ -65,6 +72,14 @@ struct CommandlineOpts {
     #[clap(short, long, name = "in-place")]
     in_place: bool,
 
+    /// Format the ```ruby code blocks in Markdown files. Directories are searched for `.md` files too, and STDIN is read as Markdown.
+    #[clap(long, conflicts_with = "erb")]
+    markdown: bool,
+
+    /// Format the Ruby code in ERB templates. Directories are searched for `.erb` files too, and STDIN is read as ERB.
+    #[clap(long)]
+    erb: bool,
+
     /// Paths for rubyfmt to analyze. By default the output will be printed to STDOUT. See `--in-place` to write files back in-place.
     /// Acceptable paths are:{n}
     /// - File paths (i.e lib/foo/bar.rb){n}
@@ -73,6 +88,32 @@ struct CommandlineOpts {
     /// rubyfmt will use these as input.{n}
     #[clap(name = "include-paths")]
     include_paths: Vec<String>,
+
+    /// The path of the file being read from STDIN. It's used to find `.rubyfmt.json` and match its `files` globs.
+    #[clap(long, name = "stdin-filepath")]
+    stdin_filepath: Option<PathBuf>,
+
+    /// Always format in this process, even if a `rubyfmt daemon` is running.
+    #[clap(long, name = "no-daemon")]
+    no_daemon: bool,
+
+    #[clap(subcommand)]
+    command: Option<RubyfmtCommand>,
+
+    /// Settings from each `.rubyfmt.json`, loaded as files need them
+    #[clap(skip)]
+    configs: config::Configs,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum RubyfmtCommand {
+    /// Keep a warm rubyfmt running in the background, listening on a Unix domain socket.
+    /// Other rubyfmt invocations will send their files to it instead of starting up their own ruby VM.
+    Daemon {
+        /// The socket to listen on. Defaults to $RUBYFMT_DAEMON_SOCKET, or a per user socket in the runtime directory.
+        #[clap(long)]
+        socket: Option<PathBuf>,
+    },
 }
 
 /******************************************************/
@@ -164,14 +205,63 @@ fn handle_execution_error(opts: &CommandlineOpts, err: ExecutionError) {
 /* Rubyfmt Integration                                */
 /******************************************************/
 
-fn rubyfmt_string(
-    &CommandlineOpts {
-        header_opt_in,
-        header_opt_out,
-        ..
-    }: &CommandlineOpts,
-    buffer: &str,
-) -> Result<Option<String>, rubyfmt::RichFormatError> {
+/// Where formatting actually happens, either in this process or in a
+/// running `rubyfmt daemon`. If the daemon stops answering, everything from
+/// then on is formatted in this process.
+enum Formatter {
+    InProcess,
+    #[cfg(unix)]
+    Daemon(RefCell<Option<daemon::Client>>),
+}
+
+impl Formatter {
+    fn format(
+        &self,
+        file_path: &Path,
+        buffer: &str,
+        options: rubyfmt::FormatOptions,
+    ) -> Result<String, rubyfmt::RichFormatError> {
+        match self {
+            Formatter::InProcess => rubyfmt::format_buffer_with_options(buffer, &options),
+            #[cfg(unix)]
+            Formatter::Daemon(client) => {
+                let mut client = client.borrow_mut();
+                if let Some(connected) = client.as_mut() {
+                    let res = connected.format(&daemon::Request {
+                        source: buffer.to_string(),
+                        path: Some(file_path.display().to_string()),
+                        options: daemon::RequestOptions {
+                            format: options.clone(),
+                            ..daemon::RequestOptions::default()
+                        },
+                    });
+                    match res {
+                        Ok(res) => {
+                            return res.map(|output| output.unwrap_or_else(|| buffer.to_string()))
+                        }
+                        Err(_) => {
+                            *client = None;
+                            initialize_ruby_vm();
+                        }
+                    }
+                }
+                rubyfmt::format_buffer_with_options(buffer, &options)
+            }
+        }
+    }
+}
+
+fn format_options_for(opts: &CommandlineOpts, file_path: &Path) -> rubyfmt::FormatOptions {
+    match opts.configs.for_file(file_path) {
+        Ok(config) => config.format_options_for(file_path),
+        Err((e, config_path)) => {
+            handle_io_error(e, &config_path.display().to_string(), ErrorExit::Exit);
+            rubyfmt::FormatOptions::default()
+        }
+    }
+}
+
+fn header_allows_formatting(header_opt_in: bool, header_opt_out: bool, buffer: &str) -> bool {
     if header_opt_in || header_opt_out {
         // Only look at the first 500 bytes for the magic header.
         // This is for performance
@@ -194,20 +284,123 @@ fn rubyfmt_string(
         // If opted in to magic "# rubyfmt: true" header and true is not
         // in the file, return early
         if header_opt_in && Some("true") != matched {
-            return Ok(None);
+            return false;
         }
 
         // If opted in to magic "# rubyfmt: false" header and false is
         // in the file, return early
         if header_opt_out && Some("false") == matched {
-            return Ok(None);
+            return false;
+        }
+    }
+
+    true
+}
+
+fn rubyfmt_string(
+    opts: &CommandlineOpts,
+    formatter: &Formatter,
+    file_path: &Path,
+    buffer: &str,
+) -> Result<Option<String>, rubyfmt::RichFormatError> {
+    if !header_allows_formatting(opts.header_opt_in, opts.header_opt_out, buffer) {
+        return Ok(None);
+    }
+
+    formatter
+        .format(file_path, buffer, format_options_for(opts, file_path))
+        .map(Some)
+}
+
+/// Formats a piece of a larger file, like a Markdown code block, which never
+/// gets the magic comments a whole file would
+fn rubyfmt_snippet(
+    opts: &CommandlineOpts,
+    formatter: &Formatter,
+    file_path: &Path,
+    buffer: &str,
+) -> Result<Option<String>, rubyfmt::RichFormatError> {
+    if !header_allows_formatting(opts.header_opt_in, opts.header_opt_out, buffer) {
+        return Ok(None);
+    }
+
+    let mut options = format_options_for(opts, file_path);
+    options.magic_comments = Default::default();
+    formatter.format(file_path, buffer, options).map(Some)
+}
+
+fn snippet_error_exit(opts: &CommandlineOpts) -> ErrorExit {
+    if opts.fail_fast {
+        ErrorExit::Exit
+    } else {
+        ErrorExit::NoExit
+    }
+}
+
+fn is_markdown_input(opts: &CommandlineOpts, file_path: &Path) -> bool {
+    opts.markdown && (opts.include_paths.is_empty() || markdown::is_markdown_path(file_path))
+}
+
+fn is_erb_input(opts: &CommandlineOpts, file_path: &Path) -> bool {
+    opts.erb && (opts.include_paths.is_empty() || erb::is_erb_path(file_path))
+}
+
+fn rubyfmt_markdown(
+    opts: &CommandlineOpts,
+    formatter: &Formatter,
+    file_path: &Path,
+    buffer: &str,
+) -> String {
+    markdown::format_ruby_blocks(buffer, &mut |line, code| {
+        match rubyfmt_snippet(opts, formatter, file_path, code) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                // A broken block is left alone, the rest of the document still gets formatted
+                let source = format!("{}:{}", file_path.display(), line);
+                handle_rubyfmt_error(e, &source, snippet_error_exit(opts));
+                None
+            }
+        }
+    })
+}
+
+fn rubyfmt_erb(
+    opts: &CommandlineOpts,
+    formatter: &Formatter,
+    file_path: &Path,
+    buffer: &str,
+) -> String {
+    erb::format_erb(buffer, &mut |line, code| {
+        match rubyfmt_snippet(opts, formatter, file_path, code) {
+            Ok(formatted) => formatted,
+            // Tags like `<% if x %>` only parse together with the tags after them
+            Err(rubyfmt::RichFormatError::SyntaxError) => None,
+            Err(e) => {
+                let source = format!("{}:{}", file_path.display(), line);
+                handle_rubyfmt_error(e, &source, snippet_error_exit(opts));
+                None
+            }
+        }
+    })
+}
+
+fn initialize_rubyfmt(opts: &CommandlineOpts) -> Formatter {
+    #[cfg(unix)]
+    {
+        if !opts.no_daemon {
+            if let Some(client) = daemon::Client::connect(&daemon::default_socket_path()) {
+                return Formatter::Daemon(RefCell::new(Some(client)));
+            }
         }
     }
+    #[cfg(not(unix))]
+    let _ = opts;
 
-    rubyfmt::format_buffer(buffer).map(Some)
+    initialize_ruby_vm();
+    Formatter::InProcess
 }
 
-fn initialize_rubyfmt() {
+fn initialize_ruby_vm() {
     let res = rubyfmt::rubyfmt_init();
     if res != rubyfmt::InitStatus::OK as libc::c_int {
         panic!(
@@ -217,6 +410,35 @@ fn initialize_rubyfmt() {
     }
 }
 
+#[cfg(unix)]
+fn run_daemon(socket: &Option<PathBuf>) {
+    let socket_path = socket.clone().unwrap_or_else(daemon::default_socket_path);
+
+    initialize_ruby_vm();
+    let res = daemon::serve(&socket_path, &|request| {
+        let options = &request.options;
+        if !header_allows_formatting(
+            options.header_opt_in,
+            options.header_opt_out,
+            &request.source,
+        ) {
+            return Ok(None);
+        }
+
+        rubyfmt::format_buffer_with_options(&request.source, &options.format).map(Some)
+    });
+
+    if let Err(e) = res {
+        handle_io_error(e, &socket_path.display().to_string(), ErrorExit::Exit);
+    }
+}
+
+#[cfg(not(unix))]
+fn run_daemon(_socket: &Option<PathBuf>) {
+    print_error("`rubyfmt daemon` is only supported on unix platforms", None);
+    exit(rubyfmt::FormatError::IOError as i32);
+}
+
 /******************************************************/
 /* Helpers                                            */
 /******************************************************/
@@ -286,7 +508,11 @@ fn iterate_input_files(opts: &CommandlineOpts, f: &dyn Fn((&Path, &String))) {
         io::stdin()
             .read_to_string(&mut buffer)
             .expect("reading from stdin to not fail");
-        f((Path::new("stdin"), &buffer))
+        let file_path = opts
+            .stdin_filepath
+            .as_deref()
+            .unwrap_or_else(|| Path::new("stdin"));
+        f((file_path, &buffer))
     } else {
         let mut file_paths = Vec::new();
         let mut dir_paths = Vec::new();
@@ -325,7 +551,11 @@ fn iterate_input_files(opts: &CommandlineOpts, f: &dyn Fn((&Path, &String))) {
                         let file_path = pp.path();
 
                         if file_path.is_file()
-                            && file_path.extension().and_then(OsStr::to_str) == Some("rb")
+                            && (matches!(
+                                file_path.extension().and_then(OsStr::to_str),
+                                Some("rb" | "rbi")
+                            ) || (opts.markdown && markdown::is_markdown_path(file_path))
+                                || (opts.erb && erb::is_erb_path(file_path)))
                         {
                             let buffer_res = read_to_string(file_path);
 
@@ -347,17 +577,25 @@ fn iterate_input_files(opts: &CommandlineOpts, f: &dyn Fn((&Path, &String))) {
 
 type FormattingFunc<'a> = &'a dyn Fn((&Path, &String, Option<String>));
 
-fn iterate_formatted(opts: &CommandlineOpts, f: FormattingFunc) {
-    iterate_input_files(
-        opts,
-        &|(file_path, before)| match rubyfmt_string(opts, before) {
+fn iterate_formatted(opts: &CommandlineOpts, formatter: &Formatter, f: FormattingFunc) {
+    iterate_input_files(opts, &|(file_path, before)| {
+        if is_markdown_input(opts, file_path) {
+            let after = rubyfmt_markdown(opts, formatter, file_path, before);
+            return f((file_path, before, Some(after)));
+        }
+        if is_erb_input(opts, file_path) {
+            let after = rubyfmt_erb(opts, formatter, file_path, before);
+            return f((file_path, before, Some(after)));
+        }
+
+        match rubyfmt_string(opts, formatter, file_path, before) {
             Ok(r) => f((file_path, before, r)),
             Err(e) => handle_execution_error(
                 opts,
                 ExecutionError::RubyfmtError(e, file_path.display().to_string()),
             ),
-        },
-    );
+        }
+    });
 }
 
 fn puts_stdout(input: &String) {
@@ -374,22 +612,31 @@ fn main() {
 
     let opts = get_command_line_options();
 
+    if let Some(RubyfmtCommand::Daemon { socket }) = &opts.command {
+        run_daemon(socket);
+        return;
+    }
+
     match opts {
         CommandlineOpts { check: true, .. } => {
-            initialize_rubyfmt();
+            let formatter = initialize_rubyfmt(&opts);
             let text_diffs: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
 
-            iterate_formatted(&opts, &|(file_path, before, after)| match after {
-                None => {}
-                Some(fmtted) => {
-                    let diff = TextDiff::from_lines(before, &fmtted);
-                    let path_string = file_path.to_str().unwrap();
-                    text_diffs.lock().unwrap().push(format!(
-                        "{}",
-                        diff.unified_diff().header(path_string, path_string)
-                    ));
-                }
-            });
+            iterate_formatted(
+                &opts,
+                &formatter,
+                &|(file_path, before, after)| match after {
+                    None => {}
+                    Some(fmtted) => {
+                        let diff = TextDiff::from_lines(before, &fmtted);
+                        let path_string = file_path.to_str().unwrap();
+                        text_diffs.lock().unwrap().push(format!(
+                            "{}",
+                            diff.unified_diff().header(path_string, path_string)
+                        ));
+                    }
+                },
+            );
 
             let all_diffs = text_diffs.lock().unwrap();
 
@@ -409,32 +656,36 @@ fn main() {
         }
 
         CommandlineOpts { in_place: true, .. } => {
-            initialize_rubyfmt();
-            iterate_formatted(&opts, &|(file_path, before, after)| match after {
-                None => {}
-                Some(fmtted) => {
-                    if fmtted.ne(before) {
-                        let file_write = OpenOptions::new()
-                            .write(true)
-                            .truncate(true)
-                            .open(file_path)
-                            .and_then(|mut file| write!(file, "{}", fmtted));
-
-                        match file_write {
-                            Ok(_) => {}
-                            Err(e) => handle_execution_error(
-                                &opts,
-                                ExecutionError::IOError(e, file_path.display().to_string()),
-                            ),
+            let formatter = initialize_rubyfmt(&opts);
+            iterate_formatted(
+                &opts,
+                &formatter,
+                &|(file_path, before, after)| match after {
+                    None => {}
+                    Some(fmtted) => {
+                        if fmtted.ne(before) {
+                            let file_write = OpenOptions::new()
+                                .write(true)
+                                .truncate(true)
+                                .open(file_path)
+                                .and_then(|mut file| write!(file, "{}", fmtted));
+
+                            match file_write {
+                                Ok(_) => {}
+                                Err(e) => handle_execution_error(
+                                    &opts,
+                                    ExecutionError::IOError(e, file_path.display().to_string()),
+                                ),
+                            }
                         }
                     }
-                }
-            })
+                },
+            )
         }
 
         _ => {
-            initialize_rubyfmt();
-            iterate_formatted(&opts, &|(_, before, after)| match after {
+            let formatter = initialize_rubyfmt(&opts);
+            iterate_formatted(&opts, &formatter, &|(_, before, after)| match after {
                 Some(fmtted) => puts_stdout(&fmtted),
                 None => puts_stdout(before),
             })
@@ -0,0 +1,191 @@
+//! Finds the Ruby code blocks in Markdown documents, e.g.
+//!
+//! ````markdown
+//! Some prose that's left alone
+//!
+//! ```ruby
+//! a 1,2,3
+//! ```
+//! ````
+//!
+//! Only fenced blocks whose info string starts with `ruby` or `rb` are
+//! formatted, everything else in the document is kept byte for byte.
+
+use std::ffi::OsStr;
+use std::path::Path;
+
+const RUBY_LANGUAGES: &[&str] = &["ruby", "rb"];
+
+pub fn is_markdown_path(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(OsStr::to_str),
+        Some("md" | "markdown")
+    )
+}
+
+struct Fence<'a> {
+    indent: &'a str,
+    marker: char,
+    length: usize,
+    is_ruby: bool,
+}
+
+impl<'a> Fence<'a> {
+    /// `list_indent` is where the content of the list item the line is in
+    /// starts, a fence indented 4 or more spaces past it is really a line of
+    /// an indented code block
+    fn opening(line: &'a str, list_indent: usize) -> Option<Self> {
+        let rest = line.trim_start_matches(' ');
+        let indent = &line[..line.len() - rest.len()];
+        if indent.len().saturating_sub(list_indent) > 3 {
+            return None;
+        }
+        let marker = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+        let length = rest.chars().take_while(|c| *c == marker).count();
+        if length < 3 {
+            return None;
+        }
+
+        let info = rest[length..].trim();
+        // Backticks in the info string mean this is inline code, not a fence
+        if marker == '`' && info.contains('`') {
+            return None;
+        }
+        let is_ruby = info
+            .split_whitespace()
+            .next()
+            .map(|language| {
+                RUBY_LANGUAGES
+                    .iter()
+                    .any(|ruby| language.eq_ignore_ascii_case(ruby))
+            })
+            .unwrap_or(false);
+
+        Some(Fence {
+            indent,
+            marker,
+            length,
+            is_ruby,
+        })
+    }
+
+    fn is_closed_by(&self, line: &str) -> bool {
+        let rest = line.trim_start_matches(' ');
+        let length = rest.chars().take_while(|c| *c == self.marker).count();
+        length >= self.length && rest[length..].trim().is_empty()
+    }
+
+    fn format_body(
+        &self,
+        body: &[&str],
+        first_line: usize,
+        format: &mut dyn FnMut(usize, &str) -> Option<String>,
+    ) -> Option<String> {
+        // The block's own indentation (e.g. inside a list item) isn't part of the code
+        let mut code = String::new();
+        for line in body {
+            match line.strip_prefix(self.indent) {
+                Some(line) => code.push_str(line),
+                None if line.trim().is_empty() => code.push('\n'),
+                None => return None,
+            }
+        }
+        // Formatted code would mix line endings into the document
+        if code.trim().is_empty() || code.contains('\r') {
+            return None;
+        }
+
+        let formatted = format(first_line, &code)?;
+        let mut result = String::with_capacity(formatted.len());
+        for line in formatted.split_inclusive('\n') {
+            if line != "\n" {
+                result.push_str(self.indent);
+            }
+            result.push_str(line);
+        }
+        if !result.ends_with('\n') {
+            result.push('\n');
+        }
+        Some(result)
+    }
+}
+
+/// Runs `format` over the code in each Ruby block, along with the line number
+/// the code starts on. A block is left as it is when `format` returns `None`,
+/// and so is a block that's never closed.
+pub fn format_ruby_blocks(
+    markdown: &str,
+    format: &mut dyn FnMut(usize, &str) -> Option<String>,
+) -> String {
+    let mut result = String::with_capacity(markdown.len());
+    // Where the content of each list item the current line is in starts
+    let mut list_indents: Vec<usize> = Vec::new();
+    let mut lines = markdown.split_inclusive('\n').enumerate();
+    while let Some((index, line)) = lines.next() {
+        result.push_str(line);
+        let rest = line.trim_start_matches(' ');
+        // Blank lines don't end a list item
+        if !rest.trim().is_empty() {
+            let indent = line.len() - rest.len();
+            while matches!(list_indents.last(), Some(list_indent) if *list_indent > indent) {
+                list_indents.pop();
+            }
+        }
+
+        let fence = match Fence::opening(line, list_indents.last().copied().unwrap_or(0)) {
+            Some(fence) => fence,
+            None => {
+                list_indents.extend(list_item_content_indent(line));
+                continue;
+            }
+        };
+
+        // Other blocks are still skipped over, so a ```ruby inside of them isn't
+        // mistaken for a real one
+        let mut body = Vec::new();
+        let mut closing = None;
+        for (_, line) in lines.by_ref() {
+            if fence.is_closed_by(line) {
+                closing = Some(line);
+                break;
+            }
+            body.push(line);
+        }
+
+        let formatted = match closing {
+            Some(_) if fence.is_ruby => fence.format_body(&body, index + 2, format),
+            _ => None,
+        };
+        match formatted {
+            Some(formatted) => result.push_str(&formatted),
+            None => body.iter().for_each(|line| result.push_str(line)),
+        }
+        if let Some(closing) = closing {
+            result.push_str(closing);
+        }
+    }
+    result
+}
+
+/// Where the content of the list item that `line` starts begins, if it
+/// starts one
+fn list_item_content_indent(line: &str) -> Option<usize> {
+    let rest = line.trim_start_matches(' ');
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let marker_length = match rest.as_bytes().get(digits) {
+        Some(b'-' | b'*' | b'+') if digits == 0 => 1,
+        Some(b'.' | b')') if (1..=9).contains(&digits) => digits + 1,
+        _ => return None,
+    };
+
+    let after_marker = &rest[marker_length..];
+    let spaces = after_marker.len() - after_marker.trim_start_matches(' ').len();
+    let spaces = match spaces {
+        0 => return None,
+        // Any more and the content is an indented code block, which starts
+        // one space after the marker
+        1..=4 => spaces,
+        _ => 1,
+    };
+    Some(line.len() - rest.len() + marker_length + spaces)
+}